@@ -0,0 +1,424 @@
+use crate::{
+    io::open_maybe_compressed,
+    regions::{Interval, Regions},
+};
+use anyhow::{Context, bail};
+use std::{
+    collections::{HashMap, HashSet},
+    io::BufRead,
+    path::Path,
+};
+
+/// Gene annotation file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationFormat {
+    /// GTF (GTF2.2 / Ensembl / GENCODE): `key "value";` attributes
+    Gtf,
+    /// GFF3: `key=value;` attributes with ID/Parent hierarchy
+    Gff3,
+}
+
+impl AnnotationFormat {
+    /// Guess format from the file extension (.gtf, .gff, .gff3, optionally followed by .gz)
+    pub fn from_path(path: &Path) -> Result<Self, anyhow::Error> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let name = name.strip_suffix(".gz").unwrap_or(&name);
+        match name.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("gtf") => Ok(Self::Gtf),
+            Some("gff") | Some("gff3") => Ok(Self::Gff3),
+            _ => bail!(
+                "Could not determine annotation format of {} (expected a .gtf, .gff or .gff3 extension, optionally gzip compressed)",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// Strand of an annotated feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+    Unknown,
+}
+
+/// A single annotated feature (exon, CDS, UTR, ...) with the gene it belongs to
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub contig: String,
+    /// 0-based, half-open coordinates
+    pub interval: Interval,
+    pub strand: Strand,
+    pub feature_type: String,
+    pub gene_id: String,
+    pub gene_name: String,
+    pub gene_biotype: String,
+}
+
+/// Which features of an annotation to keep
+#[derive(Debug, Default, Clone)]
+pub struct AnnotationFilter {
+    /// Feature types to keep (case-insensitive). `UTR` also matches five/three prime UTR types.
+    /// Empty keeps every feature type
+    pub feature_types: HashSet<String>,
+    /// Gene biotypes to keep (e.g. protein_coding). Empty keeps all biotypes
+    pub biotypes: HashSet<String>,
+    /// Gene names or IDs to keep. Empty keeps all genes
+    pub genes: HashSet<String>,
+}
+
+impl AnnotationFilter {
+    fn keep_feature_type(&self, feature_type: &str) -> bool {
+        self.feature_types.is_empty()
+            || self.feature_types.iter().any(|wanted| {
+                wanted.eq_ignore_ascii_case(feature_type)
+                    || (wanted.eq_ignore_ascii_case("utr")
+                        && feature_type.to_ascii_lowercase().contains("utr"))
+            })
+    }
+
+    fn keep(&self, feature: &Feature) -> bool {
+        self.keep_feature_type(&feature.feature_type)
+            && (self.biotypes.is_empty() || self.biotypes.contains(&feature.gene_biotype))
+            && (self.genes.is_empty()
+                || self.genes.contains(&feature.gene_name)
+                || self.genes.contains(&feature.gene_id))
+    }
+}
+
+/// Read all features of a GTF/GFF3 file (plain or gzip compressed) that pass `filter`
+pub fn read_features(
+    path: &Path,
+    filter: &AnnotationFilter,
+) -> Result<Vec<Feature>, anyhow::Error> {
+    let format = AnnotationFormat::from_path(path)?;
+    let (reader, _) = open_maybe_compressed(path, 1)
+        .with_context(|| format!("Failed to open annotation file: {}", path.display()))?;
+
+    let mut records = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line =
+            line.with_context(|| format!("Failed to read annotation file: {}", path.display()))?;
+
+        // GFF3 embeds the reference sequences after a ##FASTA directive
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let record = parse_line(&line, format)
+            .with_context(|| format!("Invalid annotation line {} in {}", i + 1, path.display()))?;
+        records.push(record);
+    }
+
+    let features = match format {
+        AnnotationFormat::Gtf => records.into_iter().map(Record::into_feature).collect(),
+        AnnotationFormat::Gff3 => resolve_gff3_genes(records),
+    };
+
+    Ok(features
+        .into_iter()
+        .filter(|feature| filter.keep(feature))
+        .collect())
+}
+
+/// Build a region set covering every feature
+pub fn regions_from_features(features: &[Feature]) -> Regions {
+    let mut regions = Regions::default();
+    for feature in features {
+        regions.push(&feature.contig, feature.interval);
+    }
+    regions
+}
+
+/// A parsed annotation line, before gene information has been resolved
+struct Record {
+    contig: String,
+    interval: Interval,
+    strand: Strand,
+    feature_type: String,
+    attributes: HashMap<String, String>,
+}
+
+impl Record {
+    fn attribute(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter()
+            .find_map(|key| self.attributes.get(*key))
+            .map(String::as_str)
+    }
+
+    fn gene_id(&self) -> Option<&str> {
+        self.attribute(&["gene_id", "gene"])
+    }
+
+    /// Gene name from an attribute naming the gene explicitly. `Name` is only the gene name on a
+    /// GFF3 gene record itself (on transcripts and exons it names the transcript or exon)
+    fn gene_name(&self) -> Option<&str> {
+        self.attribute(&["gene_name", "gene"])
+    }
+
+    /// Gene biotype from an attribute naming it explicitly. Ensembl GFF3 `biotype` attributes
+    /// are only the gene biotype on the gene record itself (transcripts carry their own)
+    fn gene_biotype(&self) -> Option<&str> {
+        self.attribute(&["gene_type", "gene_biotype"])
+    }
+
+    fn into_feature(self) -> Feature {
+        let gene_id = self.gene_id().unwrap_or_default().to_string();
+        let gene_name = self.gene_name().unwrap_or(&gene_id).to_string();
+        let gene_biotype = self.gene_biotype().unwrap_or_default().to_string();
+        Feature {
+            contig: self.contig,
+            interval: self.interval,
+            strand: self.strand,
+            feature_type: self.feature_type,
+            gene_id,
+            gene_name,
+            gene_biotype,
+        }
+    }
+}
+
+fn parse_line(line: &str, format: AnnotationFormat) -> Result<Record, anyhow::Error> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() < 9 {
+        bail!("expected 9 tab-separated columns, found {}", fields.len());
+    }
+
+    // Annotation coordinates are 1-based and inclusive
+    let start: usize = fields[3]
+        .parse()
+        .with_context(|| format!("start is not a positive integer: '{}'", fields[3]))?;
+    let end: usize = fields[4]
+        .parse()
+        .with_context(|| format!("end is not a positive integer: '{}'", fields[4]))?;
+    if start == 0 || end < start {
+        bail!("invalid feature coordinates {start}-{end}");
+    }
+
+    let strand = match fields[6] {
+        "+" => Strand::Forward,
+        "-" => Strand::Reverse,
+        _ => Strand::Unknown,
+    };
+
+    let attributes = match format {
+        AnnotationFormat::Gtf => parse_gtf_attributes(fields[8]),
+        AnnotationFormat::Gff3 => parse_gff3_attributes(fields[8]),
+    };
+
+    Ok(Record {
+        contig: fields[0].to_string(),
+        interval: Interval {
+            start: start - 1,
+            end,
+        },
+        strand,
+        feature_type: fields[2].to_string(),
+        attributes,
+    })
+}
+
+/// `gene_id "ENSG..."; gene_name "TP53";`
+fn parse_gtf_attributes(attributes: &str) -> HashMap<String, String> {
+    attributes
+        .split(';')
+        .filter_map(|attribute| {
+            let (key, value) = attribute.trim().split_once(char::is_whitespace)?;
+            Some((key.to_string(), value.trim().trim_matches('"').to_string()))
+        })
+        .collect()
+}
+
+/// `ID=gene:ENSG...;Name=TP53;biotype=protein_coding`, with percent-encoded characters
+/// (e.g. `%3B` for ';') decoded. Multiple values stay comma-separated
+fn parse_gff3_attributes(attributes: &str) -> HashMap<String, String> {
+    attributes
+        .split(';')
+        .filter_map(|attribute| {
+            let (key, value) = attribute.trim().split_once('=')?;
+            let value = value.split(',').map(percent_decode).collect::<Vec<_>>();
+            Some((percent_decode(key), value.join(",")))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes (invalid escapes are kept as they are)
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// GFF3 exons/CDS usually only reference their transcript through `Parent`, so walk up the
+/// hierarchy to fill in gene ID, name and biotype. Explicit gene attributes (e.g. `gene_name`) are
+/// taken from the nearest ancestor that defines them; `ID`, `Name` and `biotype` only from the
+/// root (parentless) record, which is the gene
+fn resolve_gff3_genes(records: Vec<Record>) -> Vec<Feature> {
+    let by_id: HashMap<&str, &Record> = records
+        .iter()
+        .filter_map(|record| Some((record.attributes.get("ID")?.as_str(), record)))
+        .collect();
+
+    let mut features = Vec::with_capacity(records.len());
+    for record in &records {
+        let mut gene_id = record.gene_id();
+        let mut gene_name = record.gene_name();
+        let mut gene_biotype = record.gene_biotype();
+        let mut gene = None;
+
+        // Bounded walk so malformed (cyclic) hierarchies terminate
+        let mut current = record;
+        for _ in 0..8 {
+            let Some(parents) = current.attributes.get("Parent") else {
+                gene = Some(current);
+                break;
+            };
+            // Records whose parent is missing from the file have no known gene record
+            let Some(parent) = parents
+                .split(',')
+                .next()
+                .and_then(|parent| by_id.get(parent))
+            else {
+                break;
+            };
+            current = parent;
+            gene_name = gene_name.or_else(|| current.gene_name());
+            gene_biotype = gene_biotype.or_else(|| current.gene_biotype());
+            gene_id = gene_id.or_else(|| current.gene_id());
+        }
+
+        if let Some(gene) = gene {
+            let attribute = |key: &str| gene.attributes.get(key).map(String::as_str);
+            gene_id = gene_id.or_else(|| attribute("ID"));
+            gene_name = gene_name.or_else(|| attribute("Name"));
+            gene_biotype = gene_biotype.or_else(|| attribute("biotype"));
+        }
+
+        let gene_id = gene_id.unwrap_or_default().to_string();
+        features.push(Feature {
+            contig: record.contig.clone(),
+            interval: record.interval,
+            strand: record.strand,
+            feature_type: record.feature_type.clone(),
+            gene_name: gene_name.map_or_else(|| gene_id.clone(), str::to_string),
+            gene_id,
+            gene_biotype: gene_biotype.unwrap_or_default().to_string(),
+        });
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(lines: &[&str]) -> Vec<Feature> {
+        let records = lines
+            .iter()
+            .map(|line| parse_line(line, AnnotationFormat::Gff3).unwrap())
+            .collect();
+        resolve_gff3_genes(records)
+    }
+
+    #[test]
+    fn formats_from_extensions() {
+        for (path, format) in [
+            ("genes.gtf", AnnotationFormat::Gtf),
+            ("gencode.v44.annotation.GTF.gz", AnnotationFormat::Gtf),
+            ("genes.gff", AnnotationFormat::Gff3),
+            ("Homo_sapiens.GRCh38.110.gff3.gz", AnnotationFormat::Gff3),
+        ] {
+            assert_eq!(
+                AnnotationFormat::from_path(Path::new(path)).unwrap(),
+                format
+            );
+        }
+        for path in ["genes.bed", "genes.gz", "gtf"] {
+            assert!(
+                AnnotationFormat::from_path(Path::new(path)).is_err(),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn gff3_attributes_are_percent_decoded() {
+        let attributes = parse_gff3_attributes("ID=gene%3BA;Note=a%2Cb%3Dc,d;Name=50%;Alias=%zz");
+        assert_eq!(attributes["ID"], "gene;A");
+        assert_eq!(attributes["Note"], "a,b=c,d");
+        assert_eq!(attributes["Name"], "50%");
+        assert_eq!(attributes["Alias"], "%zz");
+    }
+
+    #[test]
+    fn gzip_compressed_annotation() {
+        let path =
+            std::env::temp_dir().join(format!("contextcounter-{}.gtf.gz", std::process::id()));
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(
+            &mut encoder,
+            b"1\t.\tCDS\t11\t20\t.\t+\t0\tgene_id \"G1\"; gene_name \"ABC\";\n",
+        )
+        .unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+        let features = read_features(&path, &AnnotationFilter::default());
+        std::fs::remove_file(&path).unwrap();
+
+        let features = features.unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0].gene_name, "ABC");
+        assert_eq!(features[0].interval, Interval { start: 10, end: 20 });
+    }
+
+    #[test]
+    fn gff3_gene_name_and_biotype_come_from_the_gene() {
+        let features = resolve(&[
+            "1\t.\tgene\t1\t100\t.\t+\t.\tID=gene:G1;Name=ABC;biotype=protein_coding",
+            "1\t.\tmRNA\t1\t100\t.\t+\t.\tID=transcript:T1;Parent=gene:G1;Name=ABC-201;biotype=nonsense_mediated_decay",
+            "1\t.\tCDS\t10\t20\t.\t+\t0\tID=CDS:P1;Parent=transcript:T1;Name=P1",
+        ]);
+        for feature in &features {
+            assert_eq!(feature.gene_id, "gene:G1");
+            assert_eq!(feature.gene_name, "ABC");
+            assert_eq!(feature.gene_biotype, "protein_coding");
+        }
+    }
+
+    #[test]
+    fn gff3_explicit_gene_attributes() {
+        let features = resolve(&[
+            "1\t.\tgene\t1\t100\t.\t+\t.\tID=gene-TP53;Name=TP53;gene_biotype=protein_coding",
+            "1\t.\tmRNA\t1\t100\t.\t+\t.\tID=rna-NM_1;Parent=gene-TP53;gene=TP53;Name=NM_1",
+            "1\t.\texon\t1\t50\t.\t+\t.\tID=exon-NM_1-1;Parent=rna-NM_1;Name=NM_1-1",
+        ]);
+        assert_eq!(features[2].gene_name, "TP53");
+        assert_eq!(features[2].gene_biotype, "protein_coding");
+
+        // A feature whose parent is missing has no gene record to take a name from
+        let orphan = resolve(&["1\t.\texon\t1\t50\t.\t+\t.\tParent=rna-X;Name=X-1"]);
+        assert_eq!(orphan[0].gene_name, "");
+    }
+}
@@ -0,0 +1,717 @@
+use crate::{
+    io::Compression,
+    regions::{Interval, Regions},
+};
+use noodles::{
+    bam, bgzf,
+    core::{Position, Region},
+    cram, csi, fasta,
+    sam::{
+        self,
+        alignment::{
+            Record as AlignmentRecord,
+            record::{Flags, cigar::op::Kind},
+        },
+    },
+};
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+/// First 4 bytes of a CRAM file
+const CRAM_MAGIC: &[u8; 4] = b"CRAM";
+
+/// Flags of alignments that never add depth: unmapped, secondary, QC failed or duplicate reads
+/// (as in `samtools depth`)
+const FILTERED_FLAGS: Flags = Flags::UNMAPPED
+    .union(Flags::SECONDARY)
+    .union(Flags::QC_FAIL)
+    .union(Flags::DUPLICATE);
+
+/// Thresholds for a base of a sample to be callable
+#[derive(Debug, Clone, Copy)]
+pub struct DepthFilter {
+    /// Minimum mapping quality of an alignment
+    pub min_mapq: u8,
+    /// Minimum quality of an aligned base
+    pub min_base_quality: u8,
+    /// Minimum number of passing aligned bases at a position
+    pub min_depth: f64,
+}
+
+/// Streaming reader for coordinate-sorted BAM or CRAM files, to compute callable bases from the
+/// alignments of a sample without an intermediate depth track
+pub struct AlignmentReader {
+    reader: Reader,
+    header: sam::Header,
+    references: Vec<(String, usize)>,
+}
+
+enum Reader {
+    Bam(bam::io::Reader<Box<dyn Read + Send>>),
+    Cram(cram::io::Reader<File>),
+}
+
+impl AlignmentReader {
+    /// Open a BAM or CRAM file (detected from its first bytes) and read its header. CRAM records
+    /// are decoded against `reference`, a FASTA file with a `.fai` index. BAM BGZF blocks are
+    /// decompressed on `threads` worker threads when more than one is given.
+    pub fn open(path: &Path, reference: &Path, threads: usize) -> io::Result<Self> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut reader = match detect_format(&mut file)? {
+            Format::Bam => {
+                let inner: Box<dyn Read + Send> =
+                    match NonZeroUsize::new(threads).filter(|n| n.get() > 1) {
+                        Some(workers) => Box::new(
+                            bgzf::io::MultithreadedReader::with_worker_count(workers, file),
+                        ),
+                        None => Box::new(bgzf::io::Reader::new(file)),
+                    };
+                Reader::Bam(bam::io::Reader::from(inner))
+            }
+            Format::Cram => Reader::Cram(
+                cram::io::reader::Builder::default()
+                    .set_reference_sequence_repository(reference_repository(reference)?)
+                    .build_from_path(path)?,
+            ),
+        };
+
+        let header = match &mut reader {
+            Reader::Bam(reader) => reader.read_header()?,
+            Reader::Cram(reader) => reader.read_header()?,
+        };
+        Ok(Self {
+            reader,
+            references: references(&header),
+            header,
+        })
+    }
+
+    /// Name and length of each reference sequence, in header order
+    pub fn references(&self) -> &[(String, usize)] {
+        &self.references
+    }
+
+    /// Read every alignment and return the runs of callable bases of each reference
+    pub fn callable(&mut self, filter: &DepthFilter) -> io::Result<Regions> {
+        let Self {
+            reader,
+            header,
+            references,
+        } = self;
+        let mut callable = StreamedCallable {
+            references,
+            filter,
+            regions: Regions::default(),
+            current: None,
+        };
+
+        match reader {
+            Reader::Bam(reader) => {
+                for result in reader.records() {
+                    if !callable.add(&result?, header)? {
+                        break;
+                    }
+                }
+            }
+            Reader::Cram(reader) => {
+                for result in reader.records(header) {
+                    if !callable.add(&result?, header)? {
+                        break;
+                    }
+                }
+            }
+        }
+        callable.finish()
+    }
+}
+
+/// Callable bases of each reference, from alignments read in coordinate order
+struct StreamedCallable<'a> {
+    references: &'a [(String, usize)],
+    filter: &'a DepthFilter,
+    regions: Regions,
+    /// Reference being read, and the depth along it
+    current: Option<(usize, Pileup)>,
+}
+
+impl StreamedCallable<'_> {
+    /// Add an alignment. Returns false once the unplaced reads (which come last) are reached.
+    fn add(&mut self, record: &dyn AlignmentRecord, header: &sam::Header) -> io::Result<bool> {
+        let Some(reference) = record.reference_sequence_id(header).transpose()? else {
+            return Ok(false);
+        };
+
+        let next = self.next_reference();
+        if self.current.as_ref().is_none_or(|(i, _)| *i != reference) {
+            if reference < next {
+                return Err(invalid_data("alignments are not sorted by coordinate"));
+            }
+            self.finish_current();
+            // References without alignments are callable only if no depth is required
+            for i in next..reference {
+                let pileup = self.pileup(i)?;
+                self.push(i, pileup);
+            }
+            self.current = Some((reference, self.pileup(reference)?));
+        }
+        if let Some((_, pileup)) = self.current.as_mut() {
+            pileup.add(record, self.filter)?;
+        }
+        Ok(true)
+    }
+
+    /// Finish the last reference read and any after it
+    fn finish(mut self) -> io::Result<Regions> {
+        let next = self.next_reference();
+        self.finish_current();
+        for i in next..self.references.len() {
+            let pileup = self.pileup(i)?;
+            self.push(i, pileup);
+        }
+
+        self.regions.merge();
+        Ok(self.regions)
+    }
+
+    fn next_reference(&self) -> usize {
+        self.current.as_ref().map_or(0, |(i, _)| i + 1)
+    }
+
+    fn finish_current(&mut self) {
+        if let Some((i, pileup)) = self.current.take() {
+            self.push(i, pileup);
+        }
+    }
+
+    /// An empty pileup covering the whole of the `i`th reference
+    fn pileup(&self, i: usize) -> io::Result<Pileup> {
+        let (_, length) = self
+            .references
+            .get(i)
+            .ok_or_else(|| invalid_data(format!("reference {i} is not in the header")))?;
+        let interval = Interval {
+            start: 0,
+            end: *length,
+        };
+        Ok(Pileup::new(interval, self.filter.min_depth))
+    }
+
+    fn push(&mut self, i: usize, pileup: Pileup) {
+        for run in pileup.finish() {
+            self.regions.push(&self.references[i].0, run);
+        }
+    }
+}
+
+/// Random access to the alignments of a BAM file (through its `.bai` or `.csi` index) or a CRAM
+/// file (through its `.crai` index), to compute callable bases in target regions only
+pub struct IndexedAlignments {
+    reader: IndexedReader,
+    header: sam::Header,
+    references: Vec<(String, usize)>,
+}
+
+enum IndexedReader {
+    Bam(bam::io::IndexedReader<bgzf::io::Reader<File>>),
+    Cram(cram::io::IndexedReader<File>),
+}
+
+impl IndexedAlignments {
+    /// Open a BAM or CRAM file through the index next to it: `<path>.bai`, `<stem>.bai` or
+    /// `<path>.csi` for BAM, `<path>.crai` or `<stem>.crai` for CRAM (whose records are decoded
+    /// against `reference`). Returns None if there is no index.
+    pub fn open(path: &Path, reference: &Path) -> io::Result<Option<Self>> {
+        let format = detect_format(&mut BufReader::new(File::open(path)?))?;
+        let mut reader = match format {
+            Format::Bam => {
+                let builder = bam::io::indexed_reader::Builder::default();
+                let builder = if let Some(bai_path) = index_path(path, "bai") {
+                    builder.set_index(bam::bai::fs::read(bai_path)?)
+                } else if let Some(csi_path) = index_path(path, "csi") {
+                    builder.set_index(csi::fs::read(csi_path)?)
+                } else {
+                    return Ok(None);
+                };
+                IndexedReader::Bam(builder.build_from_path(path)?)
+            }
+            Format::Cram => {
+                let Some(crai_path) = index_path(path, "crai") else {
+                    return Ok(None);
+                };
+                IndexedReader::Cram(
+                    cram::io::indexed_reader::Builder::default()
+                        .set_reference_sequence_repository(reference_repository(reference)?)
+                        .set_index(cram::crai::fs::read(crai_path)?)
+                        .build_from_path(path)?,
+                )
+            }
+        };
+
+        let header = match &mut reader {
+            IndexedReader::Bam(reader) => reader.read_header()?,
+            IndexedReader::Cram(reader) => reader.read_header()?,
+        };
+        Ok(Some(Self {
+            reader,
+            references: references(&header),
+            header,
+        }))
+    }
+
+    /// Name and length of each reference sequence, in header order
+    pub fn references(&self) -> &[(String, usize)] {
+        &self.references
+    }
+
+    /// Query the alignments overlapping each target region (which must be merged) and return the
+    /// runs of callable bases inside them
+    pub fn callable(&mut self, targets: &Regions, filter: &DepthFilter) -> io::Result<Regions> {
+        let Self {
+            reader,
+            header,
+            references,
+        } = self;
+        let mut regions = Regions::default();
+
+        for (name, length) in references.iter() {
+            let Some(intervals) = targets.get(name) else {
+                continue;
+            };
+            for interval in intervals {
+                // Targets may have been padded past the reference end
+                let Some(interval) = interval.clip(*length) else {
+                    continue;
+                };
+                let mut pileup = Pileup::new(interval, filter.min_depth);
+
+                // 1-based, inclusive
+                let region = Region::new(
+                    name.as_str(),
+                    position(interval.start + 1)?..=position(interval.end)?,
+                );
+                match reader {
+                    IndexedReader::Bam(reader) => {
+                        for result in reader.query(header, &region)?.records() {
+                            pileup.add(&result?, filter)?;
+                        }
+                    }
+                    IndexedReader::Cram(reader) => {
+                        for result in reader.query(header, &region)? {
+                            pileup.add(&result?, filter)?;
+                        }
+                    }
+                }
+
+                for run in pileup.finish() {
+                    regions.push(name, run);
+                }
+            }
+        }
+
+        regions.merge();
+        Ok(regions)
+    }
+}
+
+/// Alignment file formats that can be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Bam,
+    Cram,
+}
+
+/// Detect BAM (BGZF compressed) or CRAM input from its first bytes, without consuming them
+fn detect_format(reader: &mut BufReader<File>) -> io::Result<Format> {
+    match Compression::detect(reader)? {
+        Compression::Bgzf => Ok(Format::Bam),
+        _ if reader.fill_buf()?.starts_with(CRAM_MAGIC) => Ok(Format::Cram),
+        _ => Err(invalid_data("not a BAM or CRAM file")),
+    }
+}
+
+/// Reference sequences for decoding CRAM records, read from an indexed FASTA file
+fn reference_repository(reference: &Path) -> io::Result<fasta::Repository> {
+    let reader = fasta::io::indexed_reader::Builder::default()
+        .build_from_path(reference)
+        .map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("CRAM input needs an indexed FASTA reference (with a .fai index): {err}"),
+            )
+        })?;
+    Ok(fasta::Repository::new(
+        fasta::repository::adapters::IndexedReader::new(reader),
+    ))
+}
+
+/// Name and length of each reference sequence in a header
+fn references(header: &sam::Header) -> Vec<(String, usize)> {
+    header
+        .reference_sequences()
+        .iter()
+        .map(|(name, reference)| (name.to_string(), reference.length().get()))
+        .collect()
+}
+
+/// `sample.bam.<extension>` if it exists, otherwise `sample.<extension>`
+fn index_path(path: &Path, extension: &str) -> Option<PathBuf> {
+    let mut appended = OsString::from(path.as_os_str());
+    appended.push(".");
+    appended.push(extension);
+    [PathBuf::from(appended), path.with_extension(extension)]
+        .into_iter()
+        .find(|index_path| index_path.exists())
+}
+
+/// 1-based position of a base
+fn position(position: usize) -> io::Result<Position> {
+    Position::try_from(position).map_err(invalid_data)
+}
+
+/// Depth of passing aligned bases along an interval of a reference, from alignments added in order
+/// of position. Positions no later alignment can reach are finished as they are passed, and runs
+/// of callable bases recorded.
+struct Pileup {
+    /// First position that is not finished
+    start: usize,
+    end: usize,
+    /// Depth from `start` onwards (positions past the last entry have no depth yet)
+    depth: VecDeque<u32>,
+    min_depth: f64,
+    /// Position of the last alignment added, to check the input is sorted
+    last_position: usize,
+    callable: Vec<Interval>,
+}
+
+impl Pileup {
+    fn new(interval: Interval, min_depth: f64) -> Self {
+        Self {
+            start: interval.start,
+            end: interval.end,
+            depth: VecDeque::new(),
+            min_depth,
+            last_position: 0,
+            callable: Vec::new(),
+        }
+    }
+
+    /// Add the aligned bases of an alignment that pass the filters. Deleted and skipped reference
+    /// bases add no depth.
+    fn add(&mut self, record: &dyn AlignmentRecord, filter: &DepthFilter) -> io::Result<()> {
+        // Unplaced reads have no position
+        let Some(start) = record.alignment_start().transpose()? else {
+            return Ok(());
+        };
+        let position = usize::from(start) - 1;
+        if position < self.last_position {
+            return Err(invalid_data("alignments are not sorted by coordinate"));
+        }
+        self.last_position = position;
+        self.finish_before(position);
+
+        // A mapping quality of 255 (unavailable) passes any threshold
+        let mapping_quality = record
+            .mapping_quality()
+            .transpose()?
+            .map_or(u8::MAX, u8::from);
+        if record.flags()?.intersects(FILTERED_FLAGS) || mapping_quality < filter.min_mapq {
+            return Ok(());
+        }
+
+        // Empty if the read has no sequence, 0xFF each if qualities are missing
+        let qualities: Vec<u8> = record.quality_scores().iter().collect::<io::Result<_>>()?;
+        let (mut reference_position, mut query_position) = (position, 0);
+        for op in record.cigar().iter() {
+            let op = op?;
+            let length = op.len();
+            match op.kind() {
+                Kind::Match | Kind::SequenceMatch | Kind::SequenceMismatch => {
+                    for i in 0..length {
+                        let quality = qualities.get(query_position + i).copied();
+                        if quality.unwrap_or(u8::MAX) >= filter.min_base_quality {
+                            self.add_base(reference_position + i);
+                        }
+                    }
+                    reference_position += length;
+                    query_position += length;
+                }
+                Kind::Insertion | Kind::SoftClip => query_position += length,
+                Kind::Deletion | Kind::Skip => reference_position += length,
+                _ => {}
+            }
+            if reference_position >= self.end {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_base(&mut self, position: usize) {
+        if position < self.start || position >= self.end {
+            return;
+        }
+        let i = position - self.start;
+        if i >= self.depth.len() {
+            self.depth.resize(i + 1, 0);
+        }
+        self.depth[i] += 1;
+    }
+
+    /// Finish the positions before `position`
+    fn finish_before(&mut self, position: usize) {
+        let position = position.min(self.end);
+        while self.start < position {
+            // Past the last covered position, the rest have no depth
+            let (depth, bases) = match self.depth.pop_front() {
+                Some(depth) => (depth, 1),
+                None => (0, position - self.start),
+            };
+            if f64::from(depth) >= self.min_depth {
+                let run = Interval {
+                    start: self.start,
+                    end: self.start + bases,
+                };
+                match self.callable.last_mut() {
+                    Some(last) if last.end == run.start => last.end = run.end,
+                    _ => self.callable.push(run),
+                }
+            }
+            self.start += bases;
+        }
+    }
+
+    /// Finish every position and return the runs of callable bases
+    fn finish(mut self) -> Vec<Interval> {
+        self.finish_before(self.end);
+        self.callable
+    }
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noodles::sam::alignment::{
+        RecordBuf,
+        record::{MappingQuality, cigar::Op},
+        record_buf::QualityScores,
+    };
+
+    /// An alignment record with the given CIGAR and base qualities
+    fn record(
+        position: usize,
+        flags: u16,
+        mapq: u8,
+        cigar: &[(Kind, usize)],
+        quals: &[u8],
+    ) -> RecordBuf {
+        RecordBuf::builder()
+            .set_reference_sequence_id(0)
+            .set_alignment_start(Position::try_from(position + 1).unwrap())
+            .set_flags(Flags::from(flags))
+            .set_mapping_quality(MappingQuality::new(mapq).unwrap())
+            .set_cigar(
+                cigar
+                    .iter()
+                    .map(|&(kind, len)| Op::new(kind, len))
+                    .collect(),
+            )
+            .set_quality_scores(QualityScores::from(quals.to_vec()))
+            .build()
+    }
+
+    const FILTER: DepthFilter = DepthFilter {
+        min_mapq: 20,
+        min_base_quality: 10,
+        min_depth: 1.0,
+    };
+
+    fn interval(start: usize, end: usize) -> Interval {
+        Interval { start, end }
+    }
+
+    #[test]
+    fn cigar_operations() {
+        let mut pileup = Pileup::new(interval(0, 100), 1.0);
+        // 2S 3M 2I 2D 4N 3= : bases 10-12 and 19-21 are aligned
+        let cigar = [
+            (Kind::SoftClip, 2),
+            (Kind::Match, 3),
+            (Kind::Insertion, 2),
+            (Kind::Deletion, 2),
+            (Kind::Skip, 4),
+            (Kind::SequenceMatch, 3),
+        ];
+        pileup
+            .add(&record(10, 0, 60, &cigar, &[30; 10]), &FILTER)
+            .unwrap();
+        assert_eq!(pileup.finish(), [interval(10, 13), interval(19, 22)]);
+    }
+
+    #[test]
+    fn read_and_base_filters() {
+        let mut pileup = Pileup::new(interval(0, 100), 1.0);
+        let quals = [30, 5, 30, 30];
+        pileup
+            .add(&record(0, 0, 60, &[(Kind::Match, 4)], &quals), &FILTER)
+            .unwrap();
+        // Low mapping quality, duplicate and secondary reads add no depth
+        pileup
+            .add(&record(10, 0, 19, &[(Kind::Match, 4)], &[30; 4]), &FILTER)
+            .unwrap();
+        pileup
+            .add(
+                &record(20, 0x400, 60, &[(Kind::Match, 4)], &[30; 4]),
+                &FILTER,
+            )
+            .unwrap();
+        pileup
+            .add(
+                &record(30, 0x100, 60, &[(Kind::Match, 4)], &[30; 4]),
+                &FILTER,
+            )
+            .unwrap();
+        // Reads without qualities pass
+        pileup
+            .add(
+                &record(40, 0x800, 60, &[(Kind::Match, 2)], &[0xff; 2]),
+                &FILTER,
+            )
+            .unwrap();
+        pileup
+            .add(&record(50, 0, 60, &[(Kind::Match, 2)], &[]), &FILTER)
+            .unwrap();
+        assert_eq!(
+            pileup.finish(),
+            [
+                interval(0, 1),
+                interval(2, 4),
+                interval(40, 42),
+                interval(50, 52)
+            ]
+        );
+    }
+
+    #[test]
+    fn min_depth_and_clipping() {
+        let mut pileup = Pileup::new(interval(5, 12), 2.0);
+        pileup
+            .add(&record(0, 0, 60, &[(Kind::Match, 8)], &[30; 8]), &FILTER)
+            .unwrap();
+        pileup
+            .add(&record(6, 0, 60, &[(Kind::Match, 10)], &[30; 10]), &FILTER)
+            .unwrap();
+        assert_eq!(pileup.finish(), [interval(6, 8)]);
+
+        // Without a depth requirement every base of the interval is callable
+        let pileup = Pileup::new(interval(5, 12), 0.0);
+        assert_eq!(pileup.finish(), [interval(5, 12)]);
+    }
+
+    #[test]
+    fn unsorted_alignments() {
+        let mut pileup = Pileup::new(interval(0, 100), 1.0);
+        pileup
+            .add(&record(10, 0, 60, &[(Kind::Match, 4)], &[30; 4]), &FILTER)
+            .unwrap();
+        assert!(
+            pileup
+                .add(&record(9, 0, 60, &[(Kind::Match, 4)], &[30; 4]), &FILTER)
+                .is_err()
+        );
+    }
+
+    /// testfiles/test.bam has 3 references (chr1 40 kb, chr2 20 kb and chr3 1 kb, without
+    /// reads) and a .bai index. testfiles/test_bam_callable.bed holds its callable bases,
+    /// computed independently with these thresholds.
+    const TEST_BAM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testfiles/test.bam");
+    const BAM_FILTER: DepthFilter = DepthFilter {
+        min_mapq: 20,
+        min_base_quality: 15,
+        min_depth: 2.0,
+    };
+
+    /// Only used to decode CRAM records
+    const REFERENCE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testfiles/test.fasta");
+
+    fn expected_callable() -> Regions {
+        let bed = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testfiles/test_bam_callable.bed"
+        );
+        Regions::from_bed(Path::new(bed)).unwrap()
+    }
+
+    /// Intervals of each test BAM reference
+    fn by_reference(regions: &Regions) -> Vec<Option<&[Interval]>> {
+        ["chr1", "chr2", "chr3"]
+            .map(|name| regions.get(name))
+            .to_vec()
+    }
+
+    #[test]
+    fn read_bam_file() {
+        let expected = expected_callable();
+        for threads in [1, 3] {
+            let mut reader =
+                AlignmentReader::open(Path::new(TEST_BAM), Path::new(REFERENCE), threads).unwrap();
+            assert_eq!(
+                reader.references(),
+                [
+                    ("chr1".to_string(), 40000),
+                    ("chr2".to_string(), 20000),
+                    ("chr3".to_string(), 1000)
+                ]
+            );
+            let callable = reader.callable(&BAM_FILTER).unwrap();
+            assert_eq!(by_reference(&callable), by_reference(&expected));
+        }
+    }
+
+    #[test]
+    fn query_indexed_bam() {
+        let mut targets = Regions::default();
+        for (contig, start, end) in [
+            // Across a 16 kb linear index window
+            ("chr1", 100, 17000),
+            // Past the reference end
+            ("chr1", 39000, 40100),
+            ("chr2", 0, 20000),
+            ("chr3", 0, 500),
+            ("chrX", 0, 10),
+        ] {
+            targets.push(contig, interval(start, end));
+        }
+
+        let mut indexed = IndexedAlignments::open(Path::new(TEST_BAM), Path::new(REFERENCE))
+            .unwrap()
+            .unwrap();
+        let callable = indexed.callable(&targets, &BAM_FILTER).unwrap();
+        let expected = targets.intersect(&expected_callable());
+        assert_eq!(by_reference(&callable), by_reference(&expected));
+        assert!(callable.get("chr1").unwrap().len() > 1);
+    }
+
+    #[test]
+    fn unindexed_and_invalid_input() {
+        let path = std::env::temp_dir().join(format!("contextcounter-{}.bam", std::process::id()));
+        std::fs::copy(TEST_BAM, &path).unwrap();
+        let unindexed = IndexedAlignments::open(&path, Path::new(REFERENCE));
+        std::fs::remove_file(&path).unwrap();
+        assert!(unindexed.unwrap().is_none());
+
+        let error = AlignmentReader::open(Path::new(REFERENCE), Path::new(REFERENCE), 1)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "not a BAM or CRAM file");
+    }
+}
@@ -0,0 +1,216 @@
+use crate::counts::Counts;
+use std::fmt;
+
+/// Mutation types for each pyrimidine reference base, as used in COSMIC SBS signatures
+const SBS_ALTERNATES: [(char, [char; 3]); 2] = [('C', ['A', 'G', 'T']), ('T', ['A', 'C', 'G'])];
+
+/// The 78 COSMIC doublet base substitution channels, in COSMIC order.
+/// Substitutions of palindromic reference dinucleotides (AT, CG, GC, TA) are strand-collapsed,
+/// so those references only have 6 channels each.
+const DBS78: [&str; 78] = [
+    "AC>CA", "AC>CG", "AC>CT", "AC>GA", "AC>GG", "AC>GT", "AC>TA", "AC>TG", "AC>TT", //
+    "AT>CA", "AT>CC", "AT>CG", "AT>GA", "AT>GC", "AT>TA", //
+    "CC>AA", "CC>AG", "CC>AT", "CC>GA", "CC>GG", "CC>GT", "CC>TA", "CC>TG", "CC>TT", //
+    "CG>AT", "CG>GC", "CG>GT", "CG>TA", "CG>TC", "CG>TT", //
+    "CT>AA", "CT>AC", "CT>AG", "CT>GA", "CT>GC", "CT>GG", "CT>TA", "CT>TC", "CT>TG", //
+    "GC>AA", "GC>AG", "GC>AT", "GC>CA", "GC>CG", "GC>TA", //
+    "TA>AT", "TA>CG", "TA>CT", "TA>GC", "TA>GG", "TA>GT", //
+    "TC>AA", "TC>AG", "TC>AT", "TC>CA", "TC>CG", "TC>CT", "TC>GA", "TC>GG", "TC>GT", //
+    "TG>AA", "TG>AC", "TG>AT", "TG>CA", "TG>CC", "TG>CT", "TG>GA", "TG>GC", "TG>GT", //
+    "TT>AA", "TT>AC", "TT>AG", "TT>CA", "TT>CC", "TT>CG", "TT>GA", "TT>GC", "TT>GG",
+];
+
+/// Opportunities per single base substitution channel, built from strand-folded odd-sized
+/// contexts: SBS96 from trinucleotides (e.g. `A[C>A]A`) and SBS1536 from pentanucleotides
+/// (e.g. `AA[C>A]AA`). Each channel's opportunity is the count of its reference context.
+///
+/// Channels are sorted alphabetically, matching the row order of COSMIC / SigProfiler matrices.
+pub struct SbsChannels {
+    channels: Vec<(String, f64)>,
+}
+
+impl SbsChannels {
+    /// Expand each pyrimidine centered context into its three substitution channels.
+    /// Returns None for even-sized contexts, which have no central base.
+    pub fn new(counts: &Counts) -> Option<Self> {
+        if counts.k().is_multiple_of(2) {
+            return None;
+        }
+        let centre = counts.k() / 2;
+
+        let mut channels = Vec::with_capacity(3 * (1 << (2 * counts.k() - 1)));
+        for (ctx, cnt) in counts.weighted_contexts() {
+            let (left, rest) = ctx.split_at(centre);
+            let (reference, right) = rest.split_at(1);
+            let reference = reference.chars().next()?;
+            let (_, alternates) = SBS_ALTERNATES
+                .iter()
+                .find(|(pyrimidine, _)| *pyrimidine == reference)?;
+            for alternate in alternates {
+                channels.push((format!("{left}[{reference}>{alternate}]{right}"), cnt));
+            }
+        }
+        channels.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        Some(Self { channels })
+    }
+
+    /// Channels and their opportunities in output order
+    pub fn channels(&self) -> &[(String, f64)] {
+        &self.channels
+    }
+
+    /// Core printer: writes a two-column table with the given delimiter
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "channel{d}count", d = delim)?;
+
+        for (channel, cnt) in &self.channels {
+            writeln!(f, "{channel}{d}{cnt}", d = delim)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SbsChannels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+/// Opportunities per COSMIC doublet base substitution channel (DBS78, e.g. `AC>CA`), built from
+/// strand-folded dinucleotide counts. Each channel's opportunity is the count of its reference
+/// dinucleotide.
+pub struct DbsChannels {
+    channels: Vec<(&'static str, f64)>,
+}
+
+impl DbsChannels {
+    /// Look up the reference dinucleotide of each channel. Returns None unless `counts` holds
+    /// dinucleotides.
+    pub fn new(counts: &Counts) -> Option<Self> {
+        if counts.k() != 2 {
+            return None;
+        }
+
+        let channels = DBS78
+            .iter()
+            .map(|&channel| Some((channel, counts.weighted_count(&channel[..2])?)))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { channels })
+    }
+
+    /// Channels and their opportunities in COSMIC order
+    pub fn channels(&self) -> &[(&'static str, f64)] {
+        &self.channels
+    }
+
+    /// Core printer: writes a two-column table with the given delimiter
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "channel{d}count", d = delim)?;
+
+        for (channel, cnt) in &self.channels {
+            writeln!(f, "{channel}{d}{cnt}", d = delim)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DbsChannels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counts::{CountsDi, CountsPenta, CountsTri};
+
+    #[test]
+    fn sbs_channels_in_cosmic_order() {
+        let sbs96 = SbsChannels::new(&CountsTri::default()).unwrap();
+        let channels: Vec<&str> = sbs96.channels().iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(channels.len(), 96);
+        assert_eq!(
+            channels[..5],
+            ["A[C>A]A", "A[C>A]C", "A[C>A]G", "A[C>A]T", "A[C>G]A"]
+        );
+        assert_eq!(channels[95], "T[T>G]T");
+
+        let sbs1536 = SbsChannels::new(&CountsPenta::default()).unwrap();
+        let channels = sbs1536.channels();
+        assert_eq!(channels.len(), 1536);
+        assert_eq!(channels[0].0, "AA[C>A]AA");
+        assert_eq!(channels[1535].0, "TT[T>G]TT");
+
+        assert!(SbsChannels::new(&CountsDi::default()).is_none());
+    }
+
+    #[test]
+    fn sbs_context_counts_on_each_channel() {
+        let mut counts = CountsTri::default();
+        // ACA, and TGT on the other strand
+        counts.increment("ACA");
+        counts.increment("TGT");
+        counts.increment("GTC");
+        let sbs96 = SbsChannels::new(&counts).unwrap();
+        let nonzero: Vec<(&str, f64)> = sbs96
+            .channels()
+            .iter()
+            .filter(|(_, cnt)| *cnt > 0.0)
+            .map(|(channel, cnt)| (channel.as_str(), *cnt))
+            .collect();
+        assert_eq!(
+            nonzero,
+            [
+                ("A[C>A]A", 2.0),
+                ("A[C>G]A", 2.0),
+                ("A[C>T]A", 2.0),
+                ("G[T>A]C", 1.0),
+                ("G[T>C]C", 1.0),
+                ("G[T>G]C", 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn dbs_channels_in_cosmic_order() {
+        let mut counts = CountsDi::default();
+        // AC, and GT on the other strand; CG is palindromic
+        for dinucleotide in ["AC", "GT", "CG"] {
+            counts.increment(dinucleotide);
+        }
+        let dbs78 = DbsChannels::new(&counts).unwrap();
+        let channels = dbs78.channels();
+        assert_eq!(channels.len(), 78);
+        assert_eq!(channels[0], ("AC>CA", 2.0));
+        assert_eq!(channels[77], ("TT>GG", 0.0));
+
+        // Each reference's count is on all of its channels, 6 for palindromes and 9 otherwise
+        for (reference, expected, count) in [
+            ("AC", 9, 2.0),
+            ("AT", 6, 0.0),
+            ("CG", 6, 1.0),
+            ("GC", 6, 0.0),
+            ("TA", 6, 0.0),
+            ("TT", 9, 0.0),
+        ] {
+            let reference_channels: Vec<f64> = channels
+                .iter()
+                .filter(|(channel, _)| channel.starts_with(reference))
+                .map(|&(_, cnt)| cnt)
+                .collect();
+            assert_eq!(reference_channels, vec![count; expected], "{reference}");
+        }
+        let references: std::collections::HashSet<&str> =
+            channels.iter().map(|(channel, _)| &channel[..2]).collect();
+        assert_eq!(references.len(), 10);
+
+        assert!(DbsChannels::new(&CountsTri::default()).is_none());
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{io::Compression, regions::Interval};
+use noodles::{bgzf, fasta::fai};
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufRead, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Random access to the contigs of an indexed FASTA file: a samtools `.fai` index, plus a `.gzi`
+/// index if the file is bgzip compressed
+pub struct IndexedFasta {
+    reader: Box<dyn ReadSeek>,
+    index: fai::Index,
+}
+
+trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+impl IndexedFasta {
+    /// Open a FASTA file through the `<path>.fai` (and `<path>.gzi`) index next to it.
+    /// Returns None if an index is missing, or for plain gzip input, which cannot be indexed.
+    pub fn open(path: &Path, compression: Compression) -> io::Result<Option<Self>> {
+        let fai_path = with_appended_extension(path, "fai");
+        if !fai_path.exists() {
+            return Ok(None);
+        }
+
+        let reader: Box<dyn ReadSeek> = match compression {
+            Compression::None => Box::new(File::open(path)?),
+            Compression::Bgzf => {
+                let gzi_path = with_appended_extension(path, "gzi");
+                if !gzi_path.exists() {
+                    return Ok(None);
+                }
+                let gzi = bgzf::gzi::fs::read(gzi_path)?;
+                Box::new(bgzf::io::IndexedReader::new(File::open(path)?, gzi))
+            }
+            Compression::Gzip => return Ok(None),
+        };
+
+        Ok(Some(Self {
+            reader,
+            index: fai::fs::read(fai_path)?,
+        }))
+    }
+
+    /// Name and length of each contig, in file order
+    pub fn contigs(&self) -> Vec<(String, usize)> {
+        self.index
+            .as_ref()
+            .iter()
+            .map(|record| {
+                let name = String::from_utf8_lossy(record.name()).into_owned();
+                (name, record.length() as usize)
+            })
+            .collect()
+    }
+
+    /// Bases of an interval of the `i`th contig (in index order), clipped to the contig end
+    pub fn fetch(&mut self, i: usize, interval: Interval) -> io::Result<Vec<u8>> {
+        let record = self.index.as_ref().get(i).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "contig is not in the index")
+        })?;
+        let Some(interval) = interval.clip(record.length() as usize) else {
+            return Ok(Vec::new());
+        };
+
+        // File offset of a base, skipping the line terminators of preceding lines
+        let (line_bases, line_width) = (record.line_bases(), record.line_width());
+        let offset = |pos: u64| record.offset() + pos / line_bases * line_width + pos % line_bases;
+        let start = offset(interval.start as u64);
+        let end = offset(interval.end as u64 - 1) + 1;
+
+        let mut buf = vec![0; (end - start) as usize];
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.reader.read_exact(&mut buf)?;
+        buf.retain(|b| !b.is_ascii_whitespace());
+        Ok(buf)
+    }
+}
+
+/// `genome.fa` -> `genome.fa.<extension>`
+fn with_appended_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// A run of bases from one contig, as yielded by [`ContigBlocks::next_block`]
+#[derive(Debug, Clone, Copy)]
+pub struct Block<'a> {
+    /// Contig name (the header up to the first whitespace)
+    pub contig: &'a str,
+    /// Contig position (0-based) of the first base in `seq`
+    pub start: usize,
+    /// Number of leading bases carried over from the previous block of this contig.
+    /// Bases after these are new to this block.
+    pub carried: usize,
+    pub seq: &'a [u8],
+    /// First block of the contig
+    pub first: bool,
+    /// Last block of the contig (`start + seq.len()` is then the contig length)
+    pub last: bool,
+}
+
+/// A source of contig blocks. Blocks of a contig are yielded in order; the last block of each
+/// contig ends at the contig end.
+pub trait BlockSource {
+    /// Read the next block, or None at the end of the input
+    fn next_block(&mut self) -> io::Result<Option<Block<'_>>>;
+}
+
+/// Streaming line-based FASTA reader that yields each contig in blocks of at least `block_size`
+/// new bases (the final block of a contig may be shorter). Each block starts with the last
+/// `overlap` bases of the previous one, so windows of up to `overlap + 1` bases spanning a block
+/// boundary are still seen. Memory use is bounded by the block size rather than the contig size;
+/// use a block size of `usize::MAX` to read each contig whole.
+pub struct ContigBlocks<R> {
+    reader: R,
+    block_size: usize,
+    overlap: usize,
+    line: Vec<u8>,
+    contig: Option<String>,
+    next_contig: Option<String>,
+    seq: Vec<u8>,
+    start: usize,
+    carried: usize,
+    first: bool,
+    /// Whether the previous block handed out was a contig's last (None before the first block)
+    emitted_last: Option<bool>,
+}
+
+impl<R: BufRead> ContigBlocks<R> {
+    pub fn new(reader: R, block_size: usize, overlap: usize) -> Self {
+        Self {
+            reader,
+            block_size: block_size.max(1),
+            overlap,
+            line: Vec::new(),
+            contig: None,
+            next_contig: None,
+            seq: Vec::new(),
+            start: 0,
+            carried: 0,
+            first: true,
+            emitted_last: None,
+        }
+    }
+
+    fn emit(&mut self, last: bool) -> Block<'_> {
+        self.emitted_last = Some(last);
+        Block {
+            contig: self.contig.as_deref().unwrap_or_default(),
+            start: self.start,
+            carried: self.carried,
+            seq: &self.seq,
+            first: self.first,
+            last,
+        }
+    }
+}
+
+impl<R: BufRead> BlockSource for ContigBlocks<R> {
+    fn next_block(&mut self) -> io::Result<Option<Block<'_>>> {
+        match self.emitted_last.take() {
+            // Move on to the next contig
+            Some(true) => {
+                self.contig = self.next_contig.take();
+                self.seq.clear();
+                self.start = 0;
+                self.carried = 0;
+                self.first = true;
+            }
+            // Keep the overlap from the end of the previous block
+            Some(false) => {
+                let dropped = self.seq.len() - self.overlap.min(self.seq.len());
+                self.seq.drain(..dropped);
+                self.start += dropped;
+                self.carried = self.seq.len();
+                self.first = false;
+            }
+            None => {}
+        }
+
+        loop {
+            if self.seq.len() - self.carried >= self.block_size {
+                return Ok(Some(self.emit(false)));
+            }
+
+            self.line.clear();
+            if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+                // End of file: finish the current contig, if any
+                return Ok(self.contig.is_some().then(|| self.emit(true)));
+            }
+
+            if let Some(header) = self.line.strip_prefix(b">") {
+                let name = header
+                    .split(|b| b.is_ascii_whitespace())
+                    .next()
+                    .unwrap_or_default();
+                let name = std::str::from_utf8(name)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+                    .to_string();
+                if self.contig.is_none() {
+                    self.contig = Some(name);
+                    continue;
+                }
+                self.next_contig = Some(name);
+                return Ok(Some(self.emit(true)));
+            }
+
+            let bases = self.line.trim_ascii();
+            if bases.is_empty() {
+                continue;
+            }
+            if self.contig.is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "sequence found before the first FASTA header",
+                ));
+            }
+            self.seq.extend_from_slice(bases);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counts::Counts;
+
+    const FASTA: &[u8] =
+        b">chr1 first contig\nACGTAC\nGTNNAC\n\nGT\n>chr2\nTTAGC\n>empty\n>chr3\nA\n";
+
+    /// Each contig's name, sequence and windows of `k` bases, read in blocks of `block_size`
+    fn read(block_size: usize, k: usize) -> Vec<(String, Vec<u8>, String)> {
+        let mut blocks = ContigBlocks::new(FASTA, block_size, k - 1);
+        let mut contigs: Vec<(String, Vec<u8>, Counts)> = Vec::new();
+        while let Some(block) = blocks.next_block().unwrap() {
+            if block.first {
+                contigs.push((block.contig.to_string(), Vec::new(), Counts::new(k)));
+            }
+            let (name, seq, counts) = contigs.last_mut().unwrap();
+            assert_eq!(block.contig, name);
+            assert_eq!(block.start + block.carried, seq.len());
+            assert!(block.carried < k);
+            assert!(block.last || block.seq.len() - block.carried >= block_size);
+            seq.extend_from_slice(&block.seq[block.carried..]);
+
+            // Windows ending in the new bases
+            counts.count_sequence(&block.seq[block.carried.saturating_sub(k - 1)..]);
+        }
+        contigs
+            .into_iter()
+            .map(|(name, seq, counts)| (name, seq, counts.unfolded().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn blocks_cover_each_contig() {
+        let whole = read(usize::MAX, 3);
+        let names: Vec<&str> = whole.iter().map(|(name, ..)| name.as_str()).collect();
+        assert_eq!(names, ["chr1", "chr2", "empty", "chr3"]);
+        assert_eq!(whole[0].1, b"ACGTACGTNNACGT");
+
+        for block_size in [1, 2, 3, 5, 100] {
+            for k in [1, 2, 3, 5] {
+                let whole = read(usize::MAX, k);
+                assert_eq!(
+                    read(block_size, k),
+                    whole,
+                    "block size {block_size}, k = {k}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sequence_before_header() {
+        let mut blocks = ContigBlocks::new(&b"ACGT\n>chr1\nACGT\n"[..], 10, 2);
+        assert!(blocks.next_block().is_err());
+    }
+
+    /// Contigs wrapped at 7 bases per line: one ending mid-line, one filling its last line, and
+    /// one shorter than a line
+    const WRAPPED: &[u8] =
+        b">chr1 first\nACGTACG\nTTGCAAC\nGGTAC\n>chr2\nCCCCAAA\nAGGGGTT\n>chr3\nTAG\n";
+
+    /// Intervals fetched from one contig, and their bases
+    type Fetched = Vec<(Interval, Vec<u8>)>;
+
+    /// Write [`WRAPPED`] (plain or bgzip compressed, in blocks of 10 bytes) with its `.fai` (and
+    /// `.gzi`) index, fetch every interval of each contig (and some past its end), then remove
+    /// the files. Returns the contigs and, for each, its fetched intervals.
+    fn fetch_all(compression: Compression) -> (Vec<(String, usize)>, Vec<Fetched>) {
+        let path = std::env::temp_dir().join(format!(
+            "contextcounter-{}-{compression:?}.fa",
+            std::process::id()
+        ));
+        std::fs::write(&path, WRAPPED).unwrap();
+        let fai = noodles::fasta::fs::index(&path).unwrap();
+        fai::fs::write(with_appended_extension(&path, "fai"), &fai).unwrap();
+
+        if compression == Compression::Bgzf {
+            let mut writer = bgzf::io::Writer::new(Vec::new());
+            let mut gzi = Vec::new();
+            for (i, block) in WRAPPED.chunks(10).enumerate() {
+                if i > 0 {
+                    gzi.push((writer.position(), (i * 10) as u64));
+                }
+                std::io::Write::write_all(&mut writer, block).unwrap();
+                std::io::Write::flush(&mut writer).unwrap();
+            }
+            std::fs::write(&path, writer.finish().unwrap()).unwrap();
+            bgzf::gzi::fs::write(with_appended_extension(&path, "gzi"), &gzi.into()).unwrap();
+        }
+
+        let mut fasta = IndexedFasta::open(&path, compression).unwrap().unwrap();
+        let contigs = fasta.contigs();
+        let fetched = contigs
+            .iter()
+            .enumerate()
+            .map(|(i, (_, length))| {
+                let mut fetched = Vec::new();
+                for start in 0..length + 2 {
+                    for end in start + 1..length + 3 {
+                        let interval = Interval { start, end };
+                        fetched.push((interval, fasta.fetch(i, interval).unwrap()));
+                    }
+                }
+                fetched
+            })
+            .collect();
+
+        for extension in ["", ".fai", ".gzi"] {
+            let mut name = path.clone().into_os_string();
+            name.push(extension);
+            let _ = std::fs::remove_file(name);
+        }
+        (contigs, fetched)
+    }
+
+    #[test]
+    fn indexed_fetch_matches_full_scan() {
+        let mut blocks = ContigBlocks::new(WRAPPED, usize::MAX, 0);
+        let mut scanned = Vec::new();
+        while let Some(block) = blocks.next_block().unwrap() {
+            scanned.push((block.contig.to_string(), block.seq.to_vec()));
+        }
+
+        for compression in [Compression::None, Compression::Bgzf] {
+            let (contigs, fetched) = fetch_all(compression);
+            let lengths: Vec<(String, usize)> = scanned
+                .iter()
+                .map(|(name, seq)| (name.clone(), seq.len()))
+                .collect();
+            assert_eq!(contigs, lengths);
+
+            for ((_, seq), fetched) in scanned.iter().zip(&fetched) {
+                for (interval, bases) in fetched {
+                    // Clipped to the contig end
+                    let expected = seq.get(interval.start..interval.end.min(seq.len()));
+                    assert_eq!(
+                        bases.as_slice(),
+                        expected.unwrap_or_default(),
+                        "{compression:?} {interval:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gzip_and_unindexed_fasta_are_not_opened() {
+        let path = std::env::temp_dir().join(format!(
+            "contextcounter-{}-unindexed.fa",
+            std::process::id()
+        ));
+        std::fs::write(&path, WRAPPED).unwrap();
+        let unindexed = IndexedFasta::open(&path, Compression::None).unwrap();
+        let fai_path = with_appended_extension(&path, "fai");
+        std::fs::write(&fai_path, "chr3\t3\t5\t3\t4\n").unwrap();
+        let gzip = IndexedFasta::open(&path, Compression::Gzip).unwrap();
+        // BGZF without a .gzi index
+        let bgzf = IndexedFasta::open(&path, Compression::Bgzf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&fai_path).unwrap();
+
+        assert!(unindexed.is_none() && gzip.is_none() && bgzf.is_none());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    annotation::{Feature, Strand},
+    counts::Counts,
+    regions::{Interval, Regions, count_intervals},
+};
+use std::{collections::HashMap, fmt};
+
+/// The merged target intervals of a single gene
+#[derive(Debug, Clone)]
+pub struct Gene {
+    pub id: String,
+    pub name: String,
+    pub contig: String,
+    pub strand: Strand,
+    /// Sorted, non-overlapping intervals
+    pub intervals: Vec<Interval>,
+}
+
+/// Whether a feature is a gene record (`gene`, or e.g. Ensembl's `ncRNA_gene` and `pseudogene`)
+pub fn is_gene_record(feature: &Feature) -> bool {
+    let feature_type = feature.feature_type.to_ascii_lowercase();
+    feature_type == "gene" || feature_type == "pseudogene" || feature_type.ends_with("_gene")
+}
+
+/// The gene records of an annotation, one [`Gene`] per record span. Annotations without gene
+/// records (e.g. UCSC GTFs) fall back to grouping all features by gene. Features without a gene
+/// ID (e.g. Ensembl `biological_region` records) are ignored, as are whole-sequence GFF3 `region`
+/// records whenever gene records are present.
+pub fn gene_spans(features: &[Feature]) -> Vec<Gene> {
+    let gene_records = features.iter().any(is_gene_record);
+    genes_from_features(
+        features.iter().filter(|feature| {
+            !feature.gene_id.is_empty() && (!gene_records || is_gene_record(feature))
+        }),
+        0,
+    )
+}
+
+/// Group annotation features by gene, padding each feature by `padding` bases and merging
+/// overlaps within a gene. Genes are returned in order of first appearance.
+pub fn genes_from_features<'a>(
+    features: impl IntoIterator<Item = &'a Feature>,
+    padding: usize,
+) -> Vec<Gene> {
+    let mut index: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut genes: Vec<(Gene, Regions)> = Vec::new();
+
+    for feature in features {
+        let i = *index
+            .entry((&feature.gene_id, &feature.contig))
+            .or_insert_with(|| {
+                let gene = Gene {
+                    id: feature.gene_id.clone(),
+                    name: feature.gene_name.clone(),
+                    contig: feature.contig.clone(),
+                    strand: feature.strand,
+                    intervals: Vec::new(),
+                };
+                genes.push((gene, Regions::default()));
+                genes.len() - 1
+            });
+        genes[i].1.push(&feature.contig, feature.interval);
+    }
+
+    genes
+        .into_iter()
+        .map(|(mut gene, mut regions)| {
+            regions.pad(padding);
+            regions.merge();
+            gene.intervals = regions.get(&gene.contig).unwrap_or_default().to_vec();
+            gene
+        })
+        .collect()
+}
+
+/// Context counts for every gene, for each of a set of context sizes
+#[derive(Debug, Clone)]
+pub struct GeneCounts {
+    genes: Vec<Gene>,
+    by_contig: HashMap<String, Vec<usize>>,
+    /// One table per context size, per gene
+    counts: Vec<Vec<Counts>>,
+    ks: Vec<usize>,
+}
+
+impl GeneCounts {
+    /// Create empty tables for each gene and each context size in `ks`
+    pub fn new(genes: Vec<Gene>, ks: &[usize]) -> Self {
+        let mut by_contig: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, gene) in genes.iter().enumerate() {
+            by_contig.entry(gene.contig.clone()).or_default().push(i);
+        }
+        let counts = genes
+            .iter()
+            .map(|_| ks.iter().map(|&k| Counts::new(k)).collect())
+            .collect();
+
+        Self {
+            genes,
+            by_contig,
+            counts,
+            ks: ks.to_vec(),
+        }
+    }
+
+    /// Every gene's tables, e.g. to set their soft-mask mode
+    pub fn tables_mut(&mut self) -> impl Iterator<Item = &mut Counts> {
+        self.counts.iter_mut().flatten()
+    }
+
+    /// Number of genes
+    pub fn len(&self) -> usize {
+        self.genes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genes.is_empty()
+    }
+
+    /// Count contexts for every gene on this contig
+    pub fn count_contig(&mut self, contig: &str, seq_bytes: &[u8], flank_context: bool) {
+        let Some(genes) = self.by_contig.get(contig) else {
+            return;
+        };
+
+        for &i in genes {
+            let intervals: Vec<Interval> = self.genes[i]
+                .intervals
+                .iter()
+                .filter_map(|interval| interval.clip(seq_bytes.len()))
+                .collect();
+            for counts in &mut self.counts[i] {
+                count_intervals(seq_bytes, &intervals, flank_context, counts);
+            }
+        }
+    }
+
+    /// Long-format (gene ID, gene name, contig, context, count) table for one of the context sizes
+    pub fn table(&self, k: usize) -> Option<GeneTable<'_>> {
+        let column = self.ks.iter().position(|&size| size == k)?;
+        Some(GeneTable {
+            gene_counts: self,
+            column,
+        })
+    }
+}
+
+/// Long-format per-gene table for a single context size (see [`GeneCounts::table`])
+pub struct GeneTable<'a> {
+    gene_counts: &'a GeneCounts,
+    column: usize,
+}
+
+impl GeneTable<'_> {
+    /// Core printer: writes a five-column table with the given delimiter. Genes are identified
+    /// by ID and contig, as names need not be unique (e.g. pseudoautosomal genes on chrX and chrY)
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "gene_id{d}gene{d}contig{d}context{d}count", d = delim)?;
+
+        for (gene, counts) in self.gene_counts.genes.iter().zip(&self.gene_counts.counts) {
+            let counts = &counts[self.column];
+            let gene = format!(
+                "{id}{d}{name}{d}{contig}",
+                id = gene.id,
+                name = gene.name,
+                contig = gene.contig,
+                d = delim
+            );
+            for (ctx, cnt) in counts.weighted_contexts() {
+                writeln!(f, "{gene}{d}{ctx}{d}{cnt}", d = delim)?;
+            }
+            writeln!(f, "{gene}{d}other{d}{cnt}", d = delim, cnt = counts.other())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for GeneTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feature(gene_id: &str, name: &str, contig: &str, start: usize, end: usize) -> Feature {
+        Feature {
+            contig: contig.to_string(),
+            interval: Interval { start, end },
+            strand: Strand::Forward,
+            feature_type: "CDS".to_string(),
+            gene_id: gene_id.to_string(),
+            gene_name: name.to_string(),
+            gene_biotype: "protein_coding".to_string(),
+        }
+    }
+
+    /// SHOX on both sex chromosomes, plus a second gene on chrX
+    fn features() -> Vec<Feature> {
+        vec![
+            feature("ENSG1", "SHOX", "chrX", 2, 5),
+            feature("ENSG2", "ABC", "chrX", 10, 12),
+            feature("ENSG1", "SHOX", "chrY", 0, 3),
+            feature("ENSG1", "SHOX", "chrX", 6, 8),
+        ]
+    }
+
+    #[test]
+    fn features_grouped_by_gene_and_contig() {
+        let genes = genes_from_features(&features(), 1);
+        let summary: Vec<(&str, &str, Vec<Interval>)> = genes
+            .iter()
+            .map(|gene| {
+                (
+                    gene.id.as_str(),
+                    gene.contig.as_str(),
+                    gene.intervals.clone(),
+                )
+            })
+            .collect();
+        // Padding merges the two SHOX exons on chrX
+        assert_eq!(
+            summary,
+            [
+                ("ENSG1", "chrX", vec![Interval { start: 1, end: 9 }]),
+                ("ENSG2", "chrX", vec![Interval { start: 9, end: 13 }]),
+                ("ENSG1", "chrY", vec![Interval { start: 0, end: 4 }]),
+            ]
+        );
+    }
+
+    #[test]
+    fn count_each_gene() {
+        let mut gene_counts = GeneCounts::new(genes_from_features(&features(), 0), &[3]);
+        gene_counts.count_contig("chrX", b"AACAGTTCAGTA", false);
+        gene_counts.count_contig("chrY", b"TCAGT", false);
+        gene_counts.count_contig("chr1", b"ACGTACGT", false);
+
+        let table = gene_counts.table(3).unwrap().to_string();
+        assert!(gene_counts.table(5).is_none());
+        let rows: Vec<&str> = table.lines().filter(|row| !row.ends_with("\t0")).collect();
+        // Only SHOX 2-5 on chrX (CAG, reported as CTG) and SHOX on chrY hold a whole window
+        assert_eq!(
+            rows,
+            [
+                "gene_id\tgene\tcontig\tcontext\tcount",
+                "ENSG1\tSHOX\tchrX\tCTG\t1",
+                "ENSG1\tSHOX\tchrY\tTCA\t1",
+            ]
+        );
+        assert_eq!(table.lines().count(), 1 + 3 * 33);
+    }
+}
@@ -0,0 +1,559 @@
+use crate::regions::Interval;
+use rayon::prelude::*;
+use std::{fmt, ops::Range};
+
+/// Homopolymer and repeat lengths at or above this are pooled (the "6+" class of ID83 deletions)
+const MAX_COPIES: usize = 6;
+
+/// Repeat unit and deletion lengths at or above this are pooled (the "5+" classes of ID83), as
+/// are microhomology lengths and the run lengths and copies of insertions
+const MAX_LENGTH: usize = 5;
+
+/// Longest repeat unit and deletion scanned for the "5+" classes
+const MAX_SCANNED_LENGTH: usize = 20;
+
+/// Repeat units of 2 to 20 bases are tallied, units of 5 or more in the "5+" class of ID83
+const REPEAT_UNITS: std::ops::RangeInclusive<usize> = 2..=MAX_SCANNED_LENGTH;
+
+/// Deletions of 2 to 20 bases are checked for microhomology, those of 5 or more in the "5+" class
+const DELETION_LENGTHS: std::ops::RangeInclusive<usize> = 2..=MAX_SCANNED_LENGTH;
+
+/// Contig positions scanned by each parallel task. Structures are tallied by the task holding
+/// their first base, so results are identical for any number of threads.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Indel (ID83) opportunities, tallied from the homopolymer and repeat structure of the sequence:
+/// - homopolymer runs by base (pyrimidine folded: A runs count as T, G runs as C) and run length
+/// - tandem repeat tracts (2+ copies of a 2-20 bp unit) by unit size and number of copies
+/// - deletion sites without a repeat: deletion start positions (2-20 bp) whose deleted bases
+///   share no bases with the sequence on either side, by deletion length
+/// - microhomology sites: deletion start positions (2-20 bp) whose deleted bases share 1 or more
+///   (but not all) bases with the sequence on either side, by deletion and microhomology length
+/// - single base insertion sites next to no run of the inserted base: each gap between two bases
+///   counts once for each of C, G (as C) and A, T (as T) that neither neighbouring base is
+/// - single copies of a unit: windows of 2-20 bp whose neighbouring windows of the same size on
+///   either side differ from it, by unit size
+/// - insertion sites next to no copy of the inserted unit: each gap between two bases, once per
+///   unit size (so 16 times in the 5+ class, for units of 5 to 20 bp)
+///
+/// Each tally corresponds to an ID83 channel. Deletion channels count the copies that remain
+/// (e.g. a C homopolymer of 3 bases is `1:Del:C:2`, 4 copies of a 2 bp unit `2:Del:R:3`, a 3 bp
+/// deletion site without a repeat `3:Del:R:0` and a 3 bp deletion with 1 bp of microhomology
+/// `3:Del:M:1`). Insertion channels count the copies already present (a C homopolymer of 3
+/// bases is `1:Ins:C:3`, a single copy of a 2 bp unit `2:Ins:R:1`, and an insertion site next to
+/// no copy `1:Ins:C:0` or `2:Ins:R:0`). Units and deletions longer than 20 bp are not counted in
+/// the 5+ classes.
+#[derive(Debug, Clone, Default)]
+pub struct IndelCounts {
+    /// [C, T][run length 1..=6+]
+    homopolymers: [[u64; MAX_COPIES]; 2],
+    /// [C, T] insertion sites next to no run of the inserted base
+    base_insertion_sites: [u64; 2],
+    /// [unit 2..=5+][copies 1..=6+], where 1 copy counts deletion sites without a repeat
+    repeats: [[u64; MAX_COPIES]; MAX_LENGTH - 1],
+    /// [unit 2..=5+] single copies of a unit
+    single_units: [u64; MAX_LENGTH - 1],
+    /// [unit 2..=5+] insertion sites next to no copy of the inserted unit
+    unit_insertion_sites: [u64; MAX_LENGTH - 1],
+    /// [deletion length 2..=5+][microhomology length 1..=5+]
+    microhomology: [[u64; MAX_LENGTH]; MAX_LENGTH - 1],
+}
+
+impl IndelCounts {
+    /// Scan a contig. If `intervals` is supplied only structures lying entirely inside a region
+    /// are counted (or, with `flank_context`, structures that start inside a region).
+    /// The contig is split into chunks scanned in parallel on the current rayon thread pool.
+    pub fn count_contig(
+        &mut self,
+        seq_bytes: &[u8],
+        intervals: Option<&[Interval]>,
+        flank_context: bool,
+    ) {
+        self.count_in_chunks(seq_bytes, intervals, flank_context, CHUNK_SIZE);
+    }
+
+    fn count_in_chunks(
+        &mut self,
+        seq_bytes: &[u8],
+        intervals: Option<&[Interval]>,
+        flank_context: bool,
+        chunk_size: usize,
+    ) {
+        let upper: Vec<u8> = seq_bytes.iter().map(u8::to_ascii_uppercase).collect();
+        let targeted = |start: usize, end: usize| match intervals {
+            None => true,
+            Some(intervals) => {
+                // Interval (if any) containing the first base
+                let i = intervals.partition_point(|interval| interval.end <= start);
+                intervals.get(i).is_some_and(|interval| {
+                    interval.start <= start && (flank_context || end <= interval.end)
+                })
+            }
+        };
+
+        let chunks: Vec<IndelCounts> = (0..upper.len().div_ceil(chunk_size))
+            .into_par_iter()
+            .map(|i| {
+                let starts = i * chunk_size..((i + 1) * chunk_size).min(upper.len());
+                let mut counts = IndelCounts::default();
+                counts.count_homopolymers(&upper, starts.clone(), &targeted);
+                counts.count_insertion_sites(&upper, starts.clone(), &targeted);
+                counts.count_repeats(&upper, starts.clone(), &targeted);
+                counts.count_microhomology(&upper, starts, &targeted);
+                counts
+            })
+            .collect();
+        for chunk in &chunks {
+            self.merge(chunk);
+        }
+    }
+
+    /// Add another set of tallies to these (e.g. to combine chunks counted in parallel)
+    pub fn merge(&mut self, other: &IndelCounts) {
+        fn add<const N: usize>(counts: &mut [u64; N], added: &[u64; N]) {
+            for (count, added) in counts.iter_mut().zip(added) {
+                *count += added;
+            }
+        }
+        for (row, added) in self.homopolymers.iter_mut().zip(&other.homopolymers) {
+            add(row, added);
+        }
+        add(&mut self.base_insertion_sites, &other.base_insertion_sites);
+        for (row, added) in self.repeats.iter_mut().zip(&other.repeats) {
+            add(row, added);
+        }
+        add(&mut self.single_units, &other.single_units);
+        add(&mut self.unit_insertion_sites, &other.unit_insertion_sites);
+        for (row, added) in self.microhomology.iter_mut().zip(&other.microhomology) {
+            add(row, added);
+        }
+    }
+
+    /// Homopolymer runs starting at `starts`
+    fn count_homopolymers(
+        &mut self,
+        seq: &[u8],
+        starts: Range<usize>,
+        targeted: &impl Fn(usize, usize) -> bool,
+    ) {
+        // Skip the rest of a run that started before this chunk
+        let mut start = starts.start;
+        while start > 0 && start < seq.len() && seq[start] == seq[start - 1] {
+            start += 1;
+        }
+
+        while start < starts.end {
+            let base = seq[start];
+            let end = start + seq[start..].iter().take_while(|&&b| b == base).count();
+
+            let row = match base {
+                b'C' | b'G' => Some(0),
+                b'T' | b'A' => Some(1),
+                _ => None,
+            };
+            if let Some(row) = row
+                && targeted(start, end)
+            {
+                self.homopolymers[row][(end - start).min(MAX_COPIES) - 1] += 1;
+            }
+            start = end;
+        }
+    }
+
+    /// Insertion sites next to no run of the inserted base or copy of the inserted unit, at the
+    /// gaps before the bases at `starts`
+    fn count_insertion_sites(
+        &mut self,
+        seq: &[u8],
+        starts: Range<usize>,
+        targeted: &impl Fn(usize, usize) -> bool,
+    ) {
+        // Unit sizes pooled in each repeat class
+        let mut unit_sizes = [0; MAX_LENGTH - 1];
+        for unit in REPEAT_UNITS {
+            unit_sizes[unit.min(MAX_LENGTH) - 2] += 1;
+        }
+
+        for gap in starts.start.max(1)..starts.end {
+            let (before, after) = (seq[gap - 1], seq[gap]);
+            if !is_acgt(before) || !is_acgt(after) || !targeted(gap - 1, gap + 1) {
+                continue;
+            }
+            for (row, bases) in [[b'C', b'G'], [b'T', b'A']].iter().enumerate() {
+                let sites = bases
+                    .iter()
+                    .filter(|&&base| before != base && after != base)
+                    .count();
+                self.base_insertion_sites[row] += sites as u64;
+            }
+            for (sites, sizes) in self.unit_insertion_sites.iter_mut().zip(unit_sizes) {
+                *sites += sizes;
+            }
+        }
+    }
+
+    /// Repeat tracts and single copies of each unit size, starting at `starts`
+    fn count_repeats(
+        &mut self,
+        seq: &[u8],
+        starts: Range<usize>,
+        targeted: &impl Fn(usize, usize) -> bool,
+    ) {
+        for unit in REPEAT_UNITS {
+            if seq.len() < unit {
+                continue;
+            }
+            let row = unit.min(MAX_LENGTH) - 2;
+
+            // Maximal stretches where each base equals the base one unit downstream
+            let matches =
+                |k: usize| k + unit < seq.len() && seq[k] == seq[k + unit] && is_acgt(seq[k]);
+            let mut k = starts.start;
+            if k > 0 && matches(k - 1) {
+                // Skip the rest of a stretch that started before this chunk
+                while matches(k) {
+                    k += 1;
+                }
+            }
+            while k < starts.end && k + unit < seq.len() {
+                if !matches(k) {
+                    k += 1;
+                    continue;
+                }
+                let start = k;
+                while matches(k) {
+                    k += 1;
+                }
+                let end = k + unit;
+                let copies = (end - start) / unit;
+
+                // Units that are themselves repeats (e.g. ATAT, CC) belong to a shorter unit size
+                if copies >= 2 && is_primitive(&seq[start..start + unit]) && targeted(start, end) {
+                    self.repeats[row][copies.min(MAX_COPIES) - 1] += 1;
+                }
+            }
+
+            // Single copies: windows of A, C, G and T equal to neither neighbouring window
+            let mut acgt = 0;
+            let from = starts.start.saturating_sub(unit - 1);
+            for k in from..(starts.end + unit - 1).min(seq.len()) {
+                // Window ending at k
+                acgt = if is_acgt(seq[k]) { acgt + 1 } else { 0 };
+                if acgt < unit {
+                    continue;
+                }
+                let start = k + 1 - unit;
+                if start < starts.start || !targeted(start, k + 1) {
+                    continue;
+                }
+                let window = &seq[start..=k];
+                let repeated_before = start >= unit && &seq[start - unit..start] == window;
+                let repeated_after = seq.get(k + 1..k + 1 + unit) == Some(window);
+                if !repeated_before && !repeated_after {
+                    self.single_units[row] += 1;
+                }
+            }
+        }
+    }
+
+    /// Deletion sites without a repeat and microhomology sites starting at `starts`
+    fn count_microhomology(
+        &mut self,
+        seq: &[u8],
+        starts: Range<usize>,
+        targeted: &impl Fn(usize, usize) -> bool,
+    ) {
+        // Bases of A, C, G and T from each position of the chunk onwards (up to the longest
+        // deletion), so deleted bases can be checked in O(1)
+        let end = (starts.end + MAX_SCANNED_LENGTH).min(seq.len());
+        let mut acgt_run = vec![0; end.saturating_sub(starts.start) + 1];
+        for k in (starts.start..end).rev() {
+            if is_acgt(seq[k]) {
+                acgt_run[k - starts.start] = acgt_run[k + 1 - starts.start] + 1;
+            }
+        }
+
+        for length in DELETION_LENGTHS {
+            for start in starts.start..starts.end.min(seq.len().saturating_sub(length)) {
+                if acgt_run[start - starts.start] < length {
+                    continue;
+                }
+                let deleted = &seq[start..start + length];
+
+                // Shared bases with the sequence after (3') and before (5') the deletion
+                let after = &seq[start + length..];
+                let before = &seq[..start];
+                let homology_3 = deleted
+                    .iter()
+                    .zip(after)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                let homology_5 = deleted
+                    .iter()
+                    .rev()
+                    .zip(before.iter().rev())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                // Full homology means the deleted bases are a repeat unit
+                let homology = homology_3.max(homology_5);
+                let row = length.min(MAX_LENGTH) - 2;
+                if homology == 0 {
+                    if targeted(start, start + length) {
+                        self.repeats[row][0] += 1;
+                    }
+                } else if homology < length
+                    && targeted(start - homology_5, start + length + homology_3)
+                {
+                    self.microhomology[row][homology.min(MAX_LENGTH) - 1] += 1;
+                }
+            }
+        }
+    }
+
+    /// Core printer: writes a table of each structure class with its ID83 channel, in COSMIC
+    /// channel order
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "channel{d}class{d}unit{d}length{d}count", d = delim)?;
+
+        let label = |n: usize, max: usize| {
+            if n >= max {
+                format!("{max}+")
+            } else {
+                n.to_string()
+            }
+        };
+        let mut row = |channel: String, class: &str, unit: String, length: String, cnt: u64| {
+            writeln!(
+                f,
+                "{channel}{d}{class}{d}{unit}{d}{length}{d}{cnt}",
+                d = delim
+            )
+        };
+
+        // Insertions count the run length (or copies) already present, pooled from 5
+        let insertion_counts = |none: u64, single: u64, runs: &[u64; MAX_COPIES]| {
+            let mut counts = [none, single, runs[1], runs[2], runs[3], 0];
+            counts[MAX_LENGTH] = runs[MAX_LENGTH - 1..].iter().sum();
+            counts
+        };
+
+        for (i, base) in ["C", "T"].iter().enumerate() {
+            for (copies, &cnt) in self.homopolymers[i].iter().enumerate() {
+                let length = label(copies + 1, MAX_COPIES);
+                row(
+                    format!("1:Del:{base}:{copies}"),
+                    "homopolymer",
+                    base.to_string(),
+                    length,
+                    cnt,
+                )?;
+            }
+        }
+        for (i, base) in ["C", "T"].iter().enumerate() {
+            let runs = &self.homopolymers[i];
+            let counts = insertion_counts(self.base_insertion_sites[i], runs[0], runs);
+            for (copies, cnt) in counts.into_iter().enumerate() {
+                let length = label(copies, MAX_LENGTH);
+                row(
+                    format!("1:Ins:{base}:{copies}"),
+                    "homopolymer",
+                    base.to_string(),
+                    length,
+                    cnt,
+                )?;
+            }
+        }
+
+        for (i, unit) in (2..=MAX_LENGTH).enumerate() {
+            for (copies, &cnt) in self.repeats[i].iter().enumerate() {
+                let channel = format!("{unit}:Del:R:{copies}");
+                let length = label(copies + 1, MAX_COPIES);
+                row(channel, "repeat", label(unit, MAX_LENGTH), length, cnt)?;
+            }
+        }
+        for (i, unit) in (2..=MAX_LENGTH).enumerate() {
+            let tracts = &self.repeats[i];
+            let counts =
+                insertion_counts(self.unit_insertion_sites[i], self.single_units[i], tracts);
+            for (copies, cnt) in counts.into_iter().enumerate() {
+                let length = label(copies, MAX_LENGTH);
+                let channel = format!("{unit}:Ins:R:{copies}");
+                row(channel, "repeat", label(unit, MAX_LENGTH), length, cnt)?;
+            }
+        }
+
+        for (i, length) in (2..=MAX_LENGTH).enumerate() {
+            // Microhomology is always shorter than the deletion (5+ deletions can have 5+)
+            let homologies = if length < MAX_LENGTH {
+                length - 1
+            } else {
+                MAX_LENGTH
+            };
+            for (h, &cnt) in self.microhomology[i].iter().enumerate().take(homologies) {
+                let channel = format!("{length}:Del:M:{}", h + 1);
+                let homology = label(h + 1, MAX_LENGTH);
+                row(
+                    channel,
+                    "microhomology",
+                    label(length, MAX_LENGTH),
+                    homology,
+                    cnt,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for IndelCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+fn is_acgt(base: u8) -> bool {
+    matches!(base, b'A' | b'C' | b'G' | b'T')
+}
+
+/// Whether a repeat unit is not itself made of a shorter repeated unit
+fn is_primitive(unit: &[u8]) -> bool {
+    (1..unit.len())
+        .filter(|&period| unit.len().is_multiple_of(period))
+        .all(|period| unit.chunks(period).any(|chunk| chunk != &unit[..period]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(seq: &[u8]) -> IndelCounts {
+        let mut counts = IndelCounts::default();
+        counts.count_contig(seq, None, false);
+        counts
+    }
+
+    #[test]
+    fn long_repeat_units_are_pooled() {
+        // Two copies of a 6 bp unit
+        let counts = count(b"GAACGTCAACGTCG");
+        assert_eq!(counts.repeats[3][1..], [1, 0, 0, 0, 0]);
+        // No shorter unit repeats (the first column holds deletion sites without a repeat)
+        let shorter: u64 = counts.repeats[..3]
+            .iter()
+            .map(|row| row[1..].iter().sum::<u64>())
+            .sum();
+        assert_eq!(shorter, 0);
+    }
+
+    #[test]
+    fn deletion_sites_without_a_repeat() {
+        let counts = count(b"ACGT");
+        assert_eq!(counts.repeats[0][0], 2);
+        assert_eq!(counts.repeats[1][0], 1);
+        assert_eq!(counts.microhomology, [[0; MAX_LENGTH]; MAX_LENGTH - 1]);
+    }
+
+    #[test]
+    fn long_deletions_with_long_microhomology() {
+        // Deleting ACGTAC leaves ACGTA(G) after it, and deleting CACGTA leaves ACGTA before it:
+        // 5 bp of microhomology each
+        let counts = count(b"TACGTACACGTAGT");
+        assert_eq!(counts.microhomology[3][4], 2);
+    }
+
+    #[test]
+    fn insertion_channels() {
+        let table = count(b"ACCCGTT").to_string();
+        for line in [
+            // Each of the 6 gaps, for C or G where neither neighbour is the inserted base
+            "1:Ins:C:0\thomopolymer\tC\t0\t6",
+            "1:Ins:C:1\thomopolymer\tC\t1\t1",
+            "1:Ins:C:3\thomopolymer\tC\t3\t1",
+            "1:Ins:T:0\thomopolymer\tT\t0\t9",
+            "1:Ins:T:2\thomopolymer\tT\t2\t1",
+            "2:Ins:R:0\trepeat\t2\t0\t6",
+            "5:Ins:R:0\trepeat\t5+\t0\t96",
+        ] {
+            assert!(table.lines().any(|l| l == line), "{line} missing");
+        }
+
+        // Three copies of AT, and single copies of ATA/TAT
+        let counts = count(b"ATATAT");
+        assert_eq!(counts.repeats[0][2], 1);
+        assert_eq!(counts.single_units[..2], [0, 4]);
+        assert!(
+            count(b"ATATAT")
+                .to_string()
+                .contains("\n2:Ins:R:3\trepeat\t2\t3\t1\n")
+        );
+    }
+
+    #[test]
+    fn all_channels_in_cosmic_order() {
+        let table = count(b"ACGT").to_string();
+        let channels: Vec<&str> = table
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split('\t').next())
+            .collect();
+        assert_eq!(channels.len(), 83);
+        assert_eq!(channels[0], "1:Del:C:0");
+        assert_eq!(channels[12], "1:Ins:C:0");
+        assert_eq!(channels[23], "1:Ins:T:5");
+        assert_eq!(channels[24], "2:Del:R:0");
+        assert_eq!(channels[47], "5:Del:R:5");
+        assert_eq!(channels[48], "2:Ins:R:0");
+        assert_eq!(channels[71], "5:Ins:R:5");
+        assert_eq!(channels[72], "2:Del:M:1");
+        assert_eq!(channels[82], "5:Del:M:5");
+    }
+
+    #[test]
+    fn chunks_match_a_single_scan() {
+        // Pseudo-random sequence with long runs and repeats, and some Ns
+        let mut state = 12345u32;
+        let mut seq = Vec::new();
+        while seq.len() < 5000 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let r = (state >> 16) as usize;
+            let unit = &b"ACGTNacgt"[r % 9..(r % 9 + 1 + r / 9 % 3).min(9)];
+            for _ in 0..1 + r / 27 % 7 {
+                seq.extend_from_slice(unit);
+            }
+        }
+        let intervals = [
+            Interval {
+                start: 10,
+                end: 700,
+            },
+            Interval {
+                start: 701,
+                end: 2500,
+            },
+            Interval {
+                start: 3000,
+                end: 4999,
+            },
+        ];
+        for (intervals, flank_context) in [
+            (None, false),
+            (Some(&intervals[..]), false),
+            (Some(&intervals[..]), true),
+        ] {
+            let mut single = IndelCounts::default();
+            single.count_contig(&seq, intervals, flank_context);
+            for chunk_size in [1, 7, 64, 1000] {
+                let mut chunked = IndelCounts::default();
+                chunked.count_in_chunks(&seq, intervals, flank_context, chunk_size);
+                assert_eq!(
+                    chunked.to_string(),
+                    single.to_string(),
+                    "chunk size {chunk_size}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use flate2::bufread::MultiGzDecoder;
+use noodles::bgzf;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    num::NonZeroUsize,
+    path::Path,
+};
+
+/// Compression of an input file, detected from its magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    /// Blocked gzip (bgzip), a gzip variant that can be decompressed block by block in parallel
+    Bgzf,
+}
+
+impl Compression {
+    /// Detect compression from the start of a stream, without consuming any of it
+    pub fn detect(reader: &mut impl BufRead) -> io::Result<Self> {
+        let header = reader.fill_buf()?;
+        if !header.starts_with(&[0x1f, 0x8b]) {
+            return Ok(Self::None);
+        }
+        // BGZF blocks are gzip members with an extra field (FLG.FEXTRA) holding a 'BC' subfield
+        if header.len() >= 14 && header[3] & 0x04 != 0 && &header[12..14] == b"BC" {
+            Ok(Self::Bgzf)
+        } else {
+            Ok(Self::Gzip)
+        }
+    }
+}
+
+/// Open a plain, gzip or BGZF compressed file (detected by its magic bytes), e.g. a FASTA file or
+/// a bedGraph track. BGZF blocks are decompressed on `threads` worker threads when more than one
+/// is given.
+pub fn open_maybe_compressed(
+    path: &Path,
+    threads: usize,
+) -> io::Result<(Box<dyn BufRead + Send>, Compression)> {
+    let mut reader = BufReader::with_capacity(32 * 1024, File::open(path)?);
+    let compression = Compression::detect(&mut reader)?;
+
+    let reader: Box<dyn BufRead + Send> = match compression {
+        Compression::None => Box::new(reader),
+        // Gzip files may hold several concatenated members
+        Compression::Gzip => Box::new(BufReader::with_capacity(
+            32 * 1024,
+            MultiGzDecoder::new(reader),
+        )),
+        Compression::Bgzf => match NonZeroUsize::new(threads).filter(|n| n.get() > 1) {
+            Some(workers) => Box::new(bgzf::io::MultithreadedReader::with_worker_count(
+                workers, reader,
+            )),
+            None => Box::new(bgzf::io::Reader::new(reader)),
+        },
+    };
+    Ok((reader, compression))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        io::{Read, Write},
+    };
+
+    const FASTA: &[u8] = b">chr1\nACGTACGTAC\nGTAC\n>chr2\nTTTTGGGG\n";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// BGZF with a block every `block_size` bytes
+    fn bgzip(data: &[u8], block_size: usize) -> Vec<u8> {
+        let mut writer = bgzf::io::Writer::new(Vec::new());
+        for block in data.chunks(block_size) {
+            writer.write_all(block).unwrap();
+            writer.flush().unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn detect_compression() {
+        for (data, expected) in [
+            (FASTA.to_vec(), Compression::None),
+            (Vec::new(), Compression::None),
+            (gzip(FASTA), Compression::Gzip),
+            (bgzip(FASTA, 7), Compression::Bgzf),
+        ] {
+            let mut reader = io::BufReader::new(data.as_slice());
+            assert_eq!(Compression::detect(&mut reader).unwrap(), expected);
+
+            // Nothing is consumed
+            let mut read = Vec::new();
+            reader.read_to_end(&mut read).unwrap();
+            assert_eq!(read, data);
+        }
+    }
+
+    #[test]
+    fn compressed_round_trips() {
+        // Concatenated gzip members are read as one stream
+        let multi_member = [gzip(&FASTA[..12]), gzip(&FASTA[12..])].concat();
+        let inputs = [
+            (FASTA.to_vec(), Compression::None),
+            (gzip(FASTA), Compression::Gzip),
+            (multi_member, Compression::Gzip),
+            (bgzip(FASTA, 7), Compression::Bgzf),
+        ];
+        for (i, (data, expected)) in inputs.iter().enumerate() {
+            for threads in [1, 3] {
+                let path = std::env::temp_dir().join(format!(
+                    "contextcounter-{}-{i}-{threads}.fa",
+                    std::process::id()
+                ));
+                fs::write(&path, data).unwrap();
+                let opened = open_maybe_compressed(&path, threads);
+                let mut read = Vec::new();
+                let compression = opened.and_then(|(mut reader, compression)| {
+                    reader.read_to_end(&mut read)?;
+                    Ok(compression)
+                });
+                fs::remove_file(&path).unwrap();
+
+                assert_eq!(compression.unwrap(), *expected);
+                assert_eq!(read, FASTA, "input {i}, {threads} threads");
+            }
+        }
+    }
+}
@@ -1 +1,13 @@
+pub mod annotation;
+pub mod bam;
+pub mod channels;
 pub mod counts;
+pub mod fasta;
+pub mod genes;
+pub mod indels;
+pub mod io;
+pub mod regions;
+pub mod strand;
+pub mod summary;
+pub mod twobit;
+pub mod weights;
@@ -1,13 +1,31 @@
 use anyhow::Context;
-use clap::Parser;
-use contextcounter::counts::{CountsDi, CountsPenta, CountsTri};
+use clap::{ArgGroup, Parser, ValueEnum};
+use contextcounter::{
+    annotation::{AnnotationFilter, read_features, regions_from_features},
+    bam::{AlignmentReader, DepthFilter, IndexedAlignments},
+    channels::{DbsChannels, SbsChannels},
+    counts::{Counts, CountsDi, CountsPenta, CountsTri, SoftMask, count_windows},
+    fasta::{Block, BlockSource, ContigBlocks, IndexedFasta},
+    genes::{GeneCounts, gene_spans, genes_from_features},
+    indels::IndelCounts,
+    io::{Compression, open_maybe_compressed},
+    regions::{
+        Footprint, Interval, Regions, count_intervals, flank_pad, intersect_intervals,
+        subtract_intervals,
+    },
+    strand::{ReplicationStrandCounts, StrandSegments, TranscriptionalStrandCounts},
+    summary::OtherSummary,
+    twobit::TwoBitBlocks,
+    weights::{WeightTrack, WeightedCounts},
+};
 use fern::colors::ColoredLevelConfig;
-use log::info;
-use noodles::fasta;
+use log::{info, warn};
+use rayon::prelude::*;
 use std::{
     collections::HashSet,
+    fmt,
     fs::{self, File},
-    io::BufReader,
+    io::{BufWriter, Write},
     path::{Path, PathBuf},
     time::SystemTime,
 };
@@ -16,10 +34,22 @@ use std::{
 #[command(
     author,
     version = "0.0.1",
-    about = "Count frequency of di/tri/penta nucleotide sequences in a fasta file"
+    about = "Count frequency of di/tri/penta nucleotide sequences in a fasta file",
+    group(ArgGroup::new("targets").args(["regions", "annotation"])),
+    group(
+        ArgGroup::new("counted_regions")
+            .args(["regions", "annotation", "depth", "bam"])
+            .multiple(true)
+    ),
+    group(ArgGroup::new("sample_depth").args(["depth", "bam"])),
+    group(
+        ArgGroup::new("gene_annotations")
+            .args(["annotation", "transcriptional_strand"])
+            .multiple(true)
+    )
 )]
 struct Cli {
-    /// Path to the input FASTA file
+    /// Path to the input FASTA file (plain text, gzip or bgzip compressed) or UCSC .2bit file
     fasta: PathBuf,
 
     /// Folder to write count files
@@ -30,6 +60,48 @@ struct Cli {
     #[arg(short, long, default_value_t = false)]
     print_counts: bool,
 
+    /// Also write strand-unfolded tables with forward-strand counts of all 4^k contexts
+    /// (16 di-, 64 tri- and 1024 pentanucleotides) next to the folded tables
+    #[arg(long, default_value_t = false)]
+    unfolded: bool,
+
+    /// Also write opportunities per COSMIC mutation channel:
+    /// SBS96 (e.g. A[C>A]A) from trinucleotides, SBS1536 (e.g. AA[C>A]AA) from pentanucleotides
+    /// and DBS78 (e.g. AC>CA) from dinucleotides
+    #[arg(long, default_value_t = false)]
+    channels: bool,
+
+    /// Also write indel (ID83) opportunities for all 83 channels in COSMIC order: homopolymer
+    /// runs, tandem repeats, single copies, insertion and deletion sites without a repeat and
+    /// microhomology sites, each labelled with its ID83 channel (e.g. 1:Del:C:2, 1:Ins:C:3)
+    #[arg(long, default_value_t = false)]
+    indels: bool,
+
+    /// How soft-masked (lowercase) bases are counted: 'all' counts them like any other base,
+    /// 'skip' counts windows touching a lowercase base as other, and 'separate' also writes
+    /// *_unmasked and *_masked di/tri/pentanucleotide tables
+    #[arg(long, value_enum, default_value_t = SoftMaskMode::All)]
+    soft_mask: SoftMaskMode,
+
+    /// Split windows containing IUPAC ambiguity codes (e.g. S = C or G) evenly between the contexts
+    /// they could be instead of counting them as other, e.g. ASA adds 0.5 to ACA and 0.5 to AGA.
+    /// Windows containing N are still counted as other. Counts may then have decimals
+    #[arg(long, default_value_t = false)]
+    fractional_iupac: bool,
+
+    /// Per-base weight track (bedGraph, plain or gzip compressed), e.g. detection probabilities
+    /// from depth and tumour purity. Also writes *_weighted di/tri/pentanucleotide tables where
+    /// each context adds the weight of its central base instead of 1 (bases missing from the
+    /// track weigh 0). Convert bigWig tracks with bigWigToBedGraph first
+    #[arg(long, value_name = "BEDGRAPH")]
+    weights: Option<PathBuf>,
+
+    /// Also write the 'other' windows of each table by contig, split into windows containing N,
+    /// IUPAC ambiguity codes (e.g. R, Y), invalid characters or excluded soft-masked bases,
+    /// and the positions of the first invalid characters
+    #[arg(long, default_value_t = false)]
+    other_summary: bool,
+
     /// Comma-separated list of fasta entries to skip (commonly chrX,chrY,chrM)
     #[arg(long, value_name = "CONTIG1,CONTIG2", num_args = 1.., value_delimiter = ',')]
     skip: Vec<String>,
@@ -38,6 +110,167 @@ struct Cli {
     /// If not supplied will include all contigs except for those described by '--ski[' argument
     #[arg(long, value_name = "CONTIC1,CONTIG2", num_args = 1.., value_delimiter = ',')]
     include: Vec<String>,
+
+    /// BED file of target regions (e.g. exome or gene panel capture targets).
+    /// If supplied, only contexts that fall entirely within a region are counted
+    #[arg(long, value_name = "BED")]
+    regions: Option<PathBuf>,
+
+    /// Also count contexts whose central base(s) lie in a region but whose flanking bases fall outside it.
+    /// Flanking bases provide context only, their own positions are not counted.
+    /// With '--depth' or '--bam', counts contexts whose central base is callable rather than every base
+    #[arg(long, default_value_t = false, requires = "counted_regions")]
+    flank_context: bool,
+
+    /// Number of bases to pad each target region by on either side (e.g. 10 to match the callable footprint).
+    /// Regions are merged after padding so overlapping targets are never counted twice
+    #[arg(long, value_name = "BASES", default_value_t = 0, requires = "targets")]
+    padding: usize,
+
+    /// Per-base depth of a sample, from mosdepth (per-base.bed.gz) or a bedGraph. Only contexts
+    /// whose bases all have a depth of at least '--min-depth' are counted (or, with
+    /// '--flank-context', contexts whose central base does). Combined with target regions,
+    /// contexts must also be on target
+    #[arg(long, value_name = "BED.GZ|BEDGRAPH", requires = "min_depth")]
+    depth: Option<PathBuf>,
+
+    /// Coordinate-sorted BAM or CRAM of a sample, to compute callable bases from its alignments
+    /// instead of a '--depth' track. Depth counts aligned bases (not deletions) of reads passing
+    /// '--min-mapq' that have a base quality of at least '--min-base-quality', skipping unmapped,
+    /// secondary, QC failed and duplicate reads. With target regions and a .bai/.csi (or .crai)
+    /// index, only the alignments overlapping the targets are read. CRAM records are decoded
+    /// against the input FASTA, which needs a .fai index
+    #[arg(long, value_name = "BAM|CRAM", requires = "min_depth")]
+    bam: Option<PathBuf>,
+
+    /// Minimum mapping quality of a read in '--bam' to count towards depth
+    #[arg(long, value_name = "MAPQ", default_value_t = 0, requires = "bam")]
+    min_mapq: u8,
+
+    /// Minimum base quality of an aligned base in '--bam' to count towards depth
+    #[arg(long, value_name = "QUALITY", default_value_t = 0, requires = "bam")]
+    min_base_quality: u8,
+
+    /// Minimum depth for a base to be callable
+    #[arg(long, value_name = "DEPTH", requires = "sample_depth")]
+    min_depth: Option<f64>,
+
+    /// BED file of regions to leave out of every count (e.g. the ENCODE blacklist, centromeres or
+    /// low-mappability tracts). Excluded bases are subtracted from the target regions, or from
+    /// whole contigs if there are none, and the bases removed from each contig are reported
+    #[arg(long, value_name = "BED")]
+    exclude: Option<PathBuf>,
+
+    /// GTF/GFF3 gene annotation (plain or gzip compressed) to build target regions from
+    /// (e.g. all coding exons). '--padding' extends both ends of every feature: '--padding 2'
+    /// covers the canonical splice sites next to each exon, but also the 2 bases beyond the
+    /// first and last coding bases of each gene
+    #[arg(long, value_name = "GTF|GFF3")]
+    annotation: Option<PathBuf>,
+
+    /// Comma-separated list of annotation feature types to count ('UTR' also matches 5'/3' UTR types)
+    #[arg(
+        long,
+        value_name = "TYPE1,TYPE2",
+        num_args = 1..,
+        value_delimiter = ',',
+        default_value = "CDS",
+        requires = "annotation"
+    )]
+    features: Vec<String>,
+
+    /// Comma-separated list of gene biotypes to use (e.g. protein_coding). If not supplied will include all biotypes
+    #[arg(long, value_name = "BIOTYPE1,BIOTYPE2", num_args = 1.., value_delimiter = ',', requires = "gene_annotations")]
+    biotypes: Vec<String>,
+
+    /// Comma-separated list of gene names or IDs to use. If not supplied will include all genes
+    #[arg(long, value_name = "GENE1,GENE2", num_args = 1.., value_delimiter = ',', requires = "gene_annotations")]
+    genes: Vec<String>,
+
+    /// Also write long-format (gene ID, gene name, contig, context, count) tables with the contexts
+    /// of each annotated gene
+    #[arg(long, default_value_t = false, requires = "annotation")]
+    per_gene: bool,
+
+    /// GTF/GFF3 gene annotation used to split trinucleotide counts by transcriptional strand
+    /// (transcribed, untranscribed, bidirectional and non-transcribed, as in SBS288).
+    /// Gene spans are taken from the gene records of genes that pass '--biotypes' and '--genes'
+    /// (or from all of their features if the annotation has no gene records)
+    #[arg(long, value_name = "GTF|GFF3")]
+    transcriptional_strand: Option<PathBuf>,
+
+    /// BED file of replication fork directions used to split trinucleotide counts into leading and
+    /// lagging strand. Column 4 is the fork direction ('+'/'right' or '-'/'left'),
+    /// optional column 5 a replication timing bin
+    #[arg(long, value_name = "BED")]
+    replication_strand: Option<PathBuf>,
+
+    /// Number of worker threads. Contigs are split into chunks that are counted in parallel
+    /// and merged, so results are identical for any number of threads.
+    /// Bgzip compressed input is also decompressed on this many threads
+    #[arg(short, long, value_name = "THREADS", default_value_t = 1)]
+    threads: usize,
+}
+
+/// Number of sequence bases (or target region bases) counted by each parallel task
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Number of new bases read per block when streaming contigs (enough for 16 parallel chunks)
+const BLOCK_SIZE: usize = 16 * CHUNK_SIZE;
+
+/// Treatment of soft-masked (lowercase) bases in the context tables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SoftMaskMode {
+    /// Count soft-masked bases like any other base
+    All,
+    /// Count windows touching a soft-masked base as other
+    Skip,
+    /// Count all windows, and write separate tables of unmasked and masked windows
+    Separate,
+}
+
+/// Optional analyses run on each contig in the same pass as the context counts
+#[derive(Default)]
+struct ContigAnalyses {
+    /// Context counts for each annotated gene
+    gene_counts: Option<GeneCounts>,
+    /// Trinucleotide counts split by transcriptional strand
+    transcriptional_strand: Option<TranscriptionalStrandCounts>,
+    /// Trinucleotide counts split by replication strand and timing bin
+    replication_strand: Option<ReplicationStrandCounts>,
+    /// Homopolymer, repeat and microhomology opportunities for indels
+    indels: Option<IndelCounts>,
+    /// Context counts weighted by a per-base weight track
+    weighted: Option<WeightedCounts>,
+}
+
+impl ContigAnalyses {
+    /// Whether any analysis is enabled (each needs a whole contig's sequence at once)
+    fn needs_whole_contigs(&self) -> bool {
+        self.gene_counts.is_some()
+            || self.transcriptional_strand.is_some()
+            || self.replication_strand.is_some()
+            || self.indels.is_some()
+            || self.weighted.is_some()
+    }
+
+    /// Every context table of the enabled analyses, e.g. to set their soft-mask mode
+    fn tables_mut(&mut self) -> Vec<&mut Counts> {
+        let mut tables: Vec<&mut Counts> = Vec::new();
+        if let Some(gene_counts) = &mut self.gene_counts {
+            tables.extend(gene_counts.tables_mut());
+        }
+        if let Some(strand_counts) = &mut self.transcriptional_strand {
+            tables.extend(strand_counts.tables_mut());
+        }
+        if let Some(strand_counts) = &mut self.replication_strand {
+            tables.extend(strand_counts.tables_mut());
+        }
+        if let Some(weighted) = &mut self.weighted {
+            tables.extend(weighted.tables_mut());
+        }
+        tables
+    }
 }
 
 fn setup_logger() -> Result<(), fern::InitError> {
@@ -99,33 +332,476 @@ fn run() -> Result<(), anyhow::Error> {
         );
     }
 
+    let biotypes: HashSet<String> = cli.biotypes.into_iter().collect();
+    let genes: HashSet<String> = cli.genes.into_iter().collect();
+
+    // Load regions to leave out of every count
+    let excluded = match &cli.exclude {
+        Some(bed) => {
+            let mut excluded = Regions::from_bed(bed)?;
+            excluded.merge();
+            info!(
+                "Loaded {} regions to exclude from [{}]",
+                excluded.len(),
+                bed.display()
+            );
+            Some(excluded)
+        }
+        None => None,
+    };
+
+    // Load target regions (from a BED file or built from a gene annotation)
+    let mut analyses = ContigAnalyses::default();
+    let mut per_gene = None;
+    let mut regions = match (&cli.regions, &cli.annotation) {
+        (Some(bed), _) => {
+            let regions = Regions::from_bed(bed)?;
+            info!(
+                "Loaded {} target regions from [{}]",
+                regions.len(),
+                bed.display()
+            );
+            Some(regions)
+        }
+        (None, Some(annotation)) => {
+            let filter = AnnotationFilter {
+                feature_types: cli.features.into_iter().collect(),
+                biotypes: biotypes.clone(),
+                genes: genes.clone(),
+            };
+            let features = read_features(annotation, &filter)?;
+            if features.is_empty() {
+                anyhow::bail!(
+                    "No features in [{}] matched the requested feature types, biotypes and genes",
+                    annotation.display()
+                );
+            }
+            let regions = regions_from_features(&features);
+            info!(
+                "Loaded {} target regions from [{}]",
+                regions.len(),
+                annotation.display()
+            );
+
+            if cli.per_gene {
+                per_gene = Some(genes_from_features(&features, cli.padding));
+            }
+            Some(regions)
+        }
+        (None, None) => None,
+    };
+    if let Some(annotation) = &cli.transcriptional_strand {
+        let filter = AnnotationFilter {
+            feature_types: HashSet::new(),
+            biotypes: biotypes.clone(),
+            genes: genes.clone(),
+        };
+        let genes = gene_spans(&read_features(annotation, &filter)?);
+        info!(
+            "Splitting trinucleotide counts by transcriptional strand of {} genes from [{}]",
+            genes.len(),
+            annotation.display()
+        );
+        analyses.transcriptional_strand = Some(TranscriptionalStrandCounts::new(
+            StrandSegments::from_gene_spans(&genes),
+        ));
+    }
+
+    if let Some(bed) = &cli.replication_strand {
+        let replication_strand = ReplicationStrandCounts::from_bed(bed)?;
+        info!(
+            "Splitting trinucleotide counts by replication strand in {} timing bins from [{}]",
+            replication_strand.bins(),
+            bed.display()
+        );
+        analyses.replication_strand = Some(replication_strand);
+    }
+
+    if cli.indels {
+        analyses.indels = Some(IndelCounts::default());
+    }
+
+    if let Some(bedgraph) = &cli.weights {
+        let track = WeightTrack::from_bedgraph(bedgraph)?;
+        info!(
+            "Weighting context counts by {} intervals from [{}]",
+            track.len(),
+            bedgraph.display()
+        );
+        analyses.weighted = Some(WeightedCounts::new(track));
+    }
+
+    if let Some(regions) = regions.as_mut() {
+        regions.pad(cli.padding);
+        regions.merge();
+        info!(
+            "{} target regions after padding by {} bases and merging overlaps",
+            regions.len(),
+            cli.padding
+        );
+    }
+
+    // Load callable regions from a per-base depth track, or from the alignments of a sample
+    let callable = match (&cli.depth, &cli.bam, cli.min_depth) {
+        (Some(depth), _, Some(min_depth)) => {
+            let callable = Regions::from_depth(depth, min_depth)?;
+            info!(
+                "Loaded {} callable regions with depth >= {} from [{}]",
+                callable.len(),
+                min_depth,
+                depth.display()
+            );
+            Some(callable)
+        }
+        (None, Some(bam), Some(min_depth)) => {
+            let filter = DepthFilter {
+                min_mapq: cli.min_mapq,
+                min_base_quality: cli.min_base_quality,
+                min_depth,
+            };
+            let callable =
+                callable_from_alignments(bam, &fasta, regions.as_ref(), &filter, cli.threads)
+                    .with_context(|| format!("Failed to read alignments: {}", bam.display()))?;
+            info!(
+                "Found {} callable regions with depth >= {} (MAPQ >= {}, base quality >= {}) in [{}]",
+                callable.len(),
+                min_depth,
+                filter.min_mapq,
+                filter.min_base_quality,
+                bam.display()
+            );
+            Some(callable)
+        }
+        _ => None,
+    };
+
+    if let Some(mut genes) = per_gene {
+        if let Some(callable) = &callable {
+            for gene in &mut genes {
+                let callable = callable.get(&gene.contig).unwrap_or_default();
+                gene.intervals = intersect_intervals(&gene.intervals, callable);
+            }
+        }
+        if let Some(excluded) = &excluded {
+            for gene in &mut genes {
+                let exclusions = excluded.get(&gene.contig).unwrap_or_default();
+                gene.intervals = subtract_intervals(&gene.intervals, exclusions);
+            }
+        }
+        info!("Counting contexts separately for {} genes", genes.len());
+        analyses.gene_counts = Some(GeneCounts::new(genes, &[3, 5, 2]));
+    }
+
+    // Analyses count soft-masked bases and IUPAC codes like the main tables
+    for table in analyses.tables_mut() {
+        table.set_fractional_iupac(cli.fractional_iupac);
+        if cli.soft_mask == SoftMaskMode::Skip {
+            table.set_soft_mask(SoftMask::Unmasked);
+        }
+    }
+
+    // Restrict counting to callable bases (on target, if there are targets)
+    if let Some(callable) = callable {
+        regions = Some(match regions {
+            Some(targets) => targets.intersect(&callable),
+            None => callable,
+        });
+        if let Some(regions) = &regions {
+            info!(
+                "{} regions to count after keeping callable bases",
+                regions.len()
+            );
+        }
+    }
+
+    // Subtract excluded regions from the targets, or from whole contigs without targets
+    let targeted = regions.is_some();
+    if let Some(excluded) = &excluded {
+        regions
+            .get_or_insert_with(Regions::whole_genome)
+            .subtract(excluded);
+    }
+
     // Create output directory if it doesn't exist
     fs::create_dir_all(&outdir)
         .with_context(|| format!("Failed to create output directory: {}", outdir.display()))?;
 
-    let stem = fasta
+    // Compressed inputs are named after the uncompressed file (e.g. genome.fa.gz -> genome)
+    let uncompressed = match fasta.extension().and_then(|ext| ext.to_str()) {
+        Some("gz" | "bgz") => Path::new(fasta.file_stem().unwrap_or_default()),
+        _ => fasta.as_path(),
+    };
+    let stem = uncompressed
         .file_stem()
         .and_then(|s| s.to_str())
         .ok_or_else(|| anyhow::anyhow!("Invalid fasta file stem"))?;
 
     let prefix = outdir.join(stem);
 
-    let trinucleotides = count_trinucleotides(&fasta, &skip, &include, print_counts)?;
-    let pentanucleotides = count_pentanucleotides(&fasta, &skip, &include, print_counts)?;
-    let dinucleotides = count_dinucleotides(&fasta, &skip, &include, print_counts)?;
+    // Initialise counts of each context to zero
+    let mut trinucleotides = CountsTri::default();
+    let mut pentanucleotides = CountsPenta::default();
+    let mut dinucleotides = CountsDi::default();
+    if cli.soft_mask == SoftMaskMode::Skip {
+        trinucleotides.set_soft_mask(SoftMask::Unmasked);
+        pentanucleotides.set_soft_mask(SoftMask::Unmasked);
+        dinucleotides.set_soft_mask(SoftMask::Unmasked);
+    }
+
+    // Tables of only unmasked and only masked windows
+    let mut soft_mask_tables: Vec<(&str, CountsTri, CountsPenta, CountsDi)> = Vec::new();
+    if cli.soft_mask == SoftMaskMode::Separate {
+        for (label, soft_mask) in [
+            ("unmasked", SoftMask::Unmasked),
+            ("masked", SoftMask::Masked),
+        ] {
+            let mut tables = (
+                label,
+                CountsTri::default(),
+                CountsPenta::default(),
+                CountsDi::default(),
+            );
+            tables.1.set_soft_mask(soft_mask);
+            tables.2.set_soft_mask(soft_mask);
+            tables.3.set_soft_mask(soft_mask);
+            soft_mask_tables.push(tables);
+        }
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(cli.threads)
+        .build()
+        .context("Failed to start worker threads")?;
+    info!("Counting with {} threads", pool.current_num_threads());
+
+    let mut counters: Vec<&mut Counts> = vec![
+        &mut trinucleotides,
+        &mut pentanucleotides,
+        &mut dinucleotides,
+    ];
+    let mut labels: Vec<String> = ["trinucleotide", "pentanucleotide", "dinucleotide"]
+        .map(String::from)
+        .to_vec();
+    for (label, tri, penta, di) in &mut soft_mask_tables {
+        counters.extend([&mut **tri, &mut **penta, &mut **di]);
+        labels.extend(
+            ["trinucleotide", "pentanucleotide", "dinucleotide"]
+                .map(|context_type| format!("{context_type}_{label}")),
+        );
+    }
+    for counter in &mut counters {
+        counter.set_fractional_iupac(cli.fractional_iupac);
+    }
+    let counters = counters.as_mut_slice();
+
+    // Stream blocks of each contig, overlapping by enough bases to complete the largest window
+    let block_size = if analyses.needs_whole_contigs() {
+        usize::MAX
+    } else {
+        BLOCK_SIZE
+    };
+    let overlap = counters.iter().map(|counter| counter.k() - 1).max();
+    let overlap = overlap.unwrap_or_default();
+
+    info!("Fasta File: [{}]", fasta.display());
+    let is_two_bit = TwoBitBlocks::detect(&fasta)
+        .with_context(|| format!("Failed to open fasta file: {}", fasta.display()))?;
+
+    let (footprint, other_summary) = if is_two_bit {
+        info!("Reading .2bit input");
+        let mut blocks = TwoBitBlocks::open(&fasta, block_size, overlap)
+            .with_context(|| format!("Failed to read .2bit file: {}", fasta.display()))?;
+        pool.install(|| {
+            count_contexts(
+                &mut blocks,
+                &skip,
+                &include,
+                regions.as_ref(),
+                cli.flank_context,
+                counters,
+                &mut analyses,
+            )
+        })?
+    } else {
+        let (reader, compression) = open_maybe_compressed(&fasta, cli.threads)
+            .with_context(|| format!("Failed to open fasta file: {}", fasta.display()))?;
+        if compression != Compression::None {
+            info!("Decompressing {:?} input", compression);
+        }
+
+        // With target regions, fetch only the bases around them if the fasta is indexed
+        let indexed = match &regions {
+            Some(_) if targeted && !analyses.needs_whole_contigs() => {
+                IndexedFasta::open(&fasta, compression).with_context(|| {
+                    format!("Failed to read index of fasta file: {}", fasta.display())
+                })?
+            }
+            _ => None,
+        };
+
+        pool.install(|| match (indexed, &regions) {
+            (Some(mut indexed), Some(regions)) => {
+                info!("Fetching target regions through the fasta index");
+                count_indexed(
+                    &mut indexed,
+                    &skip,
+                    &include,
+                    regions,
+                    cli.flank_context,
+                    counters,
+                )
+            }
+            _ => count_contexts(
+                &mut ContigBlocks::new(reader, block_size, overlap),
+                &skip,
+                &include,
+                regions.as_ref(),
+                cli.flank_context,
+                counters,
+                &mut analyses,
+            ),
+        })?
+    };
+
+    // Display count matrices
+    if print_counts {
+        println!("{}", trinucleotides);
+        println!("{}", pentanucleotides);
+        println!("{}", dinucleotides);
+    }
 
     // Write output
     info!("Writing files to: {}", outdir.canonicalize()?.display());
     let _ = write_context_file("trinucleotide", &prefix, trinucleotides.to_string());
     let _ = write_context_file("dinucleotide", &prefix, dinucleotides.to_string());
     let _ = write_context_file("pentanucleotide", &prefix, pentanucleotides.to_string());
+    for (label, tri, penta, di) in &soft_mask_tables {
+        let _ = write_context_file(&format!("trinucleotide_{label}"), &prefix, tri);
+        let _ = write_context_file(&format!("dinucleotide_{label}"), &prefix, di);
+        let _ = write_context_file(&format!("pentanucleotide_{label}"), &prefix, penta);
+    }
+    if cli.channels {
+        if let Some(sbs96) = SbsChannels::new(&trinucleotides) {
+            let _ = write_context_file("SBS96", &prefix, sbs96);
+        }
+        if let Some(sbs1536) = SbsChannels::new(&pentanucleotides) {
+            let _ = write_context_file("SBS1536", &prefix, sbs1536);
+        }
+        if let Some(dbs78) = DbsChannels::new(&dinucleotides) {
+            let _ = write_context_file("DBS78", &prefix, dbs78);
+        }
+    }
+    if cli.unfolded {
+        let _ = write_context_file("trinucleotide_unfolded", &prefix, trinucleotides.unfolded());
+        let _ = write_context_file("dinucleotide_unfolded", &prefix, dinucleotides.unfolded());
+        let _ = write_context_file(
+            "pentanucleotide_unfolded",
+            &prefix,
+            pentanucleotides.unfolded(),
+        );
+    }
+    if cli.fractional_iupac {
+        info!(
+            "Split {} trinucleotide, {} pentanucleotide and {} dinucleotide windows containing IUPAC codes fractionally",
+            trinucleotides.ambiguous(),
+            pentanucleotides.ambiguous(),
+            dinucleotides.ambiguous()
+        );
+    }
+    for (label, other) in labels.iter().zip(other_summary.totals()).take(3) {
+        info!(
+            "Other {} windows: {} with N, {} with IUPAC codes, {} with invalid characters, {} soft-masked",
+            label, other.n, other.iupac, other.invalid, other.masked
+        );
+    }
+    if other_summary.invalid_total() > 0 {
+        warn!(
+            "{} invalid characters (not a base or IUPAC code) found",
+            other_summary.invalid_total()
+        );
+    }
+    if cli.other_summary {
+        let _ = write_context_file("other_summary", &prefix, other_summary.table(&labels));
+        let _ = write_context_file("invalid_bases", &prefix, other_summary.invalid_bases());
+    }
+    if targeted {
+        info!("Target footprint: {} bases", footprint.total());
+        let _ = write_context_file("footprint", &prefix, footprint.to_string());
+    }
+    if excluded.is_some() {
+        let excluded_bases = footprint.excluded();
+        for (contig, bases) in excluded_bases.contigs() {
+            info!("Contig: {} ({} bases excluded)", contig, bases);
+        }
+        info!("Excluded: {} bases", excluded_bases.total());
+        let _ = write_context_file("excluded", &prefix, excluded_bases);
+    }
+    if let Some(strand_counts) = &analyses.transcriptional_strand {
+        let _ = write_context_file(
+            "trinucleotide_transcriptional_strand",
+            &prefix,
+            strand_counts,
+        );
+    }
+    if let Some(strand_counts) = &analyses.replication_strand {
+        let _ = write_context_file("trinucleotide_replication_strand", &prefix, strand_counts);
+    }
+    if let Some(indels) = &analyses.indels {
+        let _ = write_context_file("ID83", &prefix, indels);
+    }
+    if let Some(weighted) = &analyses.weighted {
+        let _ = write_context_file("trinucleotide_weighted", &prefix, weighted.trinucleotides());
+        let _ = write_context_file("dinucleotide_weighted", &prefix, weighted.dinucleotides());
+        let _ = write_context_file(
+            "pentanucleotide_weighted",
+            &prefix,
+            weighted.pentanucleotides(),
+        );
+    }
+    if let Some(gene_counts) = &analyses.gene_counts {
+        for (k, context_type) in [
+            (3, "trinucleotide"),
+            (5, "pentanucleotide"),
+            (2, "dinucleotide"),
+        ] {
+            if let Some(table) = gene_counts.table(k) {
+                let _ = write_context_file(&format!("{context_type}_per_gene"), &prefix, table);
+            }
+        }
+    }
     Ok(())
 }
 
+/// Callable regions of a sample from its BAM or CRAM alignments (CRAM records are decoded against
+/// `reference`). With target regions, only the alignments overlapping them are read if the file
+/// is indexed.
+fn callable_from_alignments(
+    alignments: &Path,
+    reference: &Path,
+    targets: Option<&Regions>,
+    filter: &DepthFilter,
+    threads: usize,
+) -> Result<Regions, anyhow::Error> {
+    if let Some(targets) = targets {
+        if let Some(mut indexed) = IndexedAlignments::open(alignments, reference)? {
+            info!("Reading alignments in target regions through the alignment index");
+            return Ok(indexed.callable(targets, filter)?);
+        }
+        info!("No alignment index found, reading all alignments");
+    }
+
+    let mut reader = AlignmentReader::open(alignments, reference, threads)?;
+    let callable = reader.callable(filter)?;
+    Ok(match targets {
+        Some(targets) => callable.intersect(targets),
+        None => callable,
+    })
+}
+
 fn write_context_file(
     context_type: &str,
     prefix: &Path,
-    content: String,
+    content: impl fmt::Display,
 ) -> Result<(), anyhow::Error> {
     let base_name = prefix
         .file_name()
@@ -134,200 +810,503 @@ fn write_context_file(
 
     let filename = prefix.with_file_name(format!("{}_{}.tsv", base_name, context_type));
 
-    Ok(fs::write(filename, content)?)
+    // Stream through a buffer: per-gene tables can run to hundreds of megabytes
+    let mut writer = BufWriter::new(File::create(filename)?);
+    write!(writer, "{content}")?;
+    Ok(writer.flush()?)
 }
 
-/// Count trinucleotide contexts (pyrimidine centered)
-fn count_trinucleotides(
-    fasta: &PathBuf,
+/// Count di/tri/pentanucleotide (or any other) contexts in a single pass over a fasta or .2bit file.
+/// Each contig is streamed once in blocks and every counter slides its own window along the sequence.
+/// Windows that no block covers (e.g. those touching a .2bit N block) are counted as 'other'.
+/// If `regions` is supplied only windows inside those regions are counted
+/// (or, with `flank_context`, windows whose central base(s) are inside a region).
+/// Regions are expected to be merged; the returned footprint records what was actually counted.
+/// Any enabled `analyses` (e.g. per-gene counts) are run on each counted contig in the same pass;
+/// these need whole contigs, so `blocks` must then yield each contig as a single block.
+/// Counters and analyses run in parallel on the current rayon thread pool.
+/// Also returns the breakdown of each counter's 'other' windows by contig.
+fn count_contexts(
+    blocks: &mut impl BlockSource,
     skip: &HashSet<String>,
     include: &HashSet<String>,
-    print_counts: bool,
-) -> Result<CountsTri, anyhow::Error> {
-    // Configure windowsize
-    let windowsize: usize = 3;
+    regions: Option<&Regions>,
+    flank_context: bool,
+    counters: &mut [&mut Counts],
+    analyses: &mut ContigAnalyses,
+) -> Result<(Footprint, OtherSummary), anyhow::Error> {
+    let mut footprint = Footprint::default();
+    let mut other_summary = OtherSummary::default();
+    // Windows each counter had counted before the current contig
+    let mut counted_before = vec![0; counters.len()];
 
-    // Initialise counts of each trinucleotide to zero
-    let mut tnc_counts = contextcounter::counts::CountsTri::default();
+    while let Some(block) = blocks.next_block().context("Failed to read fasta file")? {
+        let contig_name = block.contig;
 
-    info!("Fasta File: [{}]", fasta.display());
-
-    // Open connection to fasta file
-    let conn_fasta = File::open(fasta)?;
-
-    // Attach a BufReader with a 32 KiB buffer:
-    let buf_reader = BufReader::with_capacity(32 * 1024, conn_fasta);
-
-    // 3) Create the noodles FASTA reader over that buffered reader:
-    let mut reader = fasta::io::Reader::new(buf_reader);
-
-    // Read each record (contains references to sequence names & info)
-    for result in reader.records() {
-        let record = result?;
-        let contig_name = std::str::from_utf8(record.definition().name().trim_ascii())?;
-
-        // Check if contig should be skipped (in blacklist). Commonly used to exclude sex chromosomes from counts
-        if !skip.is_empty() && skip.contains(contig_name) {
-            info!("Contig: {} (skipped: in blacklist)", contig_name);
+        if let Some(reason) = skip_reason(contig_name, skip, include, regions) {
+            if block.first {
+                info!("Contig: {} (skipped: {})", contig_name, reason);
+            }
             continue;
         }
 
-        // Check if contig should be skipped (not in whitelist).
-        if !include.is_empty() && !include.contains(contig_name) {
-            info!("Contig: {} (skipped: not in whitelist)", contig_name);
+        // Otherwise, proceed with context counting
+        if block.first {
+            info!("Contig: {}", contig_name);
+            for (before, counter) in counted_before.iter_mut().zip(counters.iter()) {
+                *before = counter.total(true);
+            }
+            other_summary.start_contig(counters);
         }
+        other_summary.scan_invalid(
+            contig_name,
+            block.start + block.carried,
+            &block.seq[block.carried..],
+        );
+        let intervals = regions.and_then(|regions| regions.get(contig_name));
 
-        // Otherwise, proceed with TNC counting
-        info!("Contig: {}", contig_name);
-        let seq_bytes = record.sequence().as_ref();
-        // info!(
-        //     "Loaded contig {}: {} bases (≈{} bytes)",
-        //     contig_name,
-        //     seq_bytes.len(),
-        //     seq_bytes.len()
-        // );
-
-        // Skip sequences shorter than the window size
-        if seq_bytes.len() < windowsize {
-            continue;
-        }
+        // Once the contig length is known, clip regions (which may have been padded past the
+        // contig end) to it
+        let clipped: Option<Vec<Interval>> = match (regions, intervals) {
+            (Some(regions), Some(intervals)) if block.last => {
+                let length = block.start + block.seq.len();
+                let clipped: Vec<Interval> = intervals
+                    .iter()
+                    .filter_map(|interval| interval.clip(length))
+                    .collect();
+                footprint.add(contig_name, &clipped);
+                footprint.add_excluded(contig_name, regions.removed(contig_name), length);
+                Some(clipped)
+            }
+            _ => None,
+        };
+
+        let ContigAnalyses {
+            gene_counts,
+            transcriptional_strand,
+            replication_strand,
+            indels,
+            weighted,
+        } = &mut *analyses;
 
-        // Sliding window of size 3
-        for window in seq_bytes.windows(windowsize) {
-            // Fetch trinucleotide sequence
-            let tri = String::from_utf8(Vec::from(window)).unwrap();
-            tnc_counts.increment(&tri);
+        rayon::scope(|s| {
+            for counter in counters.iter_mut() {
+                s.spawn(move |_| count_block(&block, intervals, flank_context, counter));
+            }
+
+            // Analyses only run when the whole contig is in a single block
+            if !(block.first && block.last) {
+                return;
+            }
+            let seq_bytes = block.seq;
+            let intervals = clipped.as_deref();
+            if let Some(gene_counts) = gene_counts.as_mut() {
+                s.spawn(move |_| gene_counts.count_contig(contig_name, seq_bytes, flank_context));
+            }
+            if let Some(strand_counts) = transcriptional_strand.as_mut() {
+                s.spawn(move |_| {
+                    strand_counts.count_contig(contig_name, seq_bytes, intervals, flank_context)
+                });
+            }
+            if let Some(strand_counts) = replication_strand.as_mut() {
+                s.spawn(move |_| {
+                    strand_counts.count_contig(contig_name, seq_bytes, intervals, flank_context)
+                });
+            }
+            if let Some(indels) = indels.as_mut() {
+                s.spawn(move |_| indels.count_contig(seq_bytes, intervals, flank_context));
+            }
+            if let Some(weighted) = weighted.as_mut() {
+                s.spawn(move |_| {
+                    weighted.count_contig(contig_name, seq_bytes, intervals, flank_context)
+                });
+            }
+        });
+
+        // Count windows that fell between blocks as other
+        if block.last {
+            let length = block.start + block.seq.len();
+            for (counter, before) in counters.iter_mut().zip(&counted_before) {
+                let expected =
+                    expected_windows(counter.k(), length, clipped.as_deref(), flank_context);
+                let counted = counter.total(true) - before;
+                counter.add_n_windows(expected.saturating_sub(counted));
+            }
+            other_summary.finish_contig(contig_name, counters);
         }
     }
 
-    // Display count matrix
-    if print_counts {
-        println!("{}", tnc_counts)
+    Ok((footprint, other_summary))
+}
+
+/// Number of windows of `k` bases in a contig (or in its clipped target regions), i.e. what
+/// [`count_block`] counts when every base of the contig is in a block
+fn expected_windows(
+    k: usize,
+    length: usize,
+    intervals: Option<&[Interval]>,
+    flank_context: bool,
+) -> u64 {
+    let windows = |bases: usize| (bases + 1).saturating_sub(k) as u64;
+    let Some(intervals) = intervals else {
+        return windows(length);
     };
 
-    Ok(tnc_counts)
+    let pad = flank_pad(k, flank_context);
+    intervals
+        .iter()
+        .map(|interval| windows(interval.flanked(pad, length).len()))
+        .sum()
 }
 
-/// Count pentanucleotide contexts (pyrimidine centered)
-fn count_pentanucleotides(
-    fasta: &PathBuf,
+/// Count contexts in target regions only, fetching the bases around each region through the fasta
+/// index instead of reading whole contigs. Gives the same counts as [`count_contexts`], though
+/// invalid characters are only looked for in the fetched bases.
+fn count_indexed(
+    fasta: &mut IndexedFasta,
     skip: &HashSet<String>,
     include: &HashSet<String>,
-    print_counts: bool,
-) -> Result<CountsPenta, anyhow::Error> {
-    // Configure windowsize
-    let windowsize: usize = 5;
+    regions: &Regions,
+    flank_context: bool,
+    counters: &mut [&mut Counts],
+) -> Result<(Footprint, OtherSummary), anyhow::Error> {
+    let mut footprint = Footprint::default();
+    let mut other_summary = OtherSummary::default();
 
-    // Initialise counts of each trinucleotide to zero
-    let mut penta_counts = contextcounter::counts::CountsPenta::default();
+    // Flanking bases needed around each region by the largest window
+    let pads = counters
+        .iter()
+        .map(|counter| flank_pad(counter.k(), flank_context));
+    let pad = pads.max().unwrap_or_default();
 
-    info!("Counting pentanucleotide contexts in [{}]", fasta.display());
-
-    // Create FASTA file reader
-    let mut reader = File::open(fasta)
-        .map(BufReader::new)
-        .map(fasta::io::Reader::new)
-        .context("Failed to read pentanucleotide counts")?;
-
-    // Read each record (contains references to sequence names & info)
-    for result in reader.records() {
-        let record = result?;
-        let contig_name = std::str::from_utf8(record.definition().name().trim_ascii())?;
-
-        // Check if contig should be skipped (in blacklist). Commonly used to exclude sex chromosomes from counts
-        if !skip.is_empty() && skip.contains(contig_name) {
-            info!("Contig: {} (skipped: in blacklist)", contig_name);
+    for (i, (contig_name, length)) in fasta.contigs().into_iter().enumerate() {
+        if let Some(reason) = skip_reason(&contig_name, skip, include, Some(regions)) {
+            info!("Contig: {} (skipped: {})", contig_name, reason);
             continue;
         }
-
-        // Check if contig should be skipped (not in whitelist).
-        if !include.is_empty() && !include.contains(contig_name) {
-            info!("Contig: {} (skipped: not in whitelist)", contig_name);
-        }
-
-        // Otherwise, proceed with pentanucleotide  counting
         info!("Contig: {}", contig_name);
+        other_summary.start_contig(counters);
 
-        let seq_bytes = record.sequence().as_ref();
+        // Clip regions (which may have been padded past the contig end) to the contig length
+        let intervals: Vec<Interval> = regions
+            .get(&contig_name)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|interval| interval.clip(length))
+            .collect();
+        footprint.add(&contig_name, &intervals);
+        footprint.add_excluded(&contig_name, regions.removed(&contig_name), length);
 
-        // Skip sequences shorter than the window size
-        if seq_bytes.len() < windowsize {
-            continue;
+        // Fetch each region with its flanks, merging any that overlap once flanked
+        let mut segments = Regions::default();
+        for interval in &intervals {
+            segments.push(&contig_name, interval.flanked(pad, length));
         }
+        segments.merge();
 
-        // Sliding window along fasta entry
-        for window in seq_bytes.windows(windowsize) {
-            // Fetch trinucleotide sequence
-            let penta = String::from_utf8(Vec::from(window)).unwrap();
-            penta_counts.increment(&penta);
+        for &segment in segments.get(&contig_name).unwrap_or_default() {
+            let seq = fasta.fetch(i, segment).with_context(|| {
+                format!(
+                    "Failed to fetch {}:{}-{}",
+                    contig_name, segment.start, segment.end
+                )
+            })?;
+            other_summary.scan_invalid(&contig_name, segment.start, &seq);
+            let block = Block {
+                contig: &contig_name,
+                start: segment.start,
+                carried: 0,
+                seq: &seq,
+                first: true,
+                last: true,
+            };
+            let intervals = Some(intervals.as_slice());
+            rayon::scope(|s| {
+                for counter in counters.iter_mut() {
+                    s.spawn(move |_| count_block(&block, intervals, flank_context, counter));
+                }
+            });
         }
+        other_summary.finish_contig(&contig_name, counters);
     }
 
-    // Display count matrix
-    if print_counts {
-        println!("{}", penta_counts)
-    };
-
-    Ok(penta_counts)
+    Ok((footprint, other_summary))
 }
 
-fn count_dinucleotides(
-    fasta: &PathBuf,
+/// Why a contig is not counted, if it isn't
+fn skip_reason(
+    contig_name: &str,
     skip: &HashSet<String>,
     include: &HashSet<String>,
-    print_counts: bool,
-) -> Result<CountsDi, anyhow::Error> {
-    // Configure windowsize
-    let windowsize: usize = 2;
+    regions: Option<&Regions>,
+) -> Option<&'static str> {
+    // Check if contig should be skipped (in blacklist). Commonly used to exclude sex chromosomes from counts
+    if !skip.is_empty() && skip.contains(contig_name) {
+        Some("in blacklist")
+    }
+    // Check if contig should be skipped (not in whitelist).
+    else if !include.is_empty() && !include.contains(contig_name) {
+        Some("not in whitelist")
+    }
+    // Check if contig should be skipped (no target regions)
+    else if regions.is_some_and(|regions| regions.get(contig_name).is_none()) {
+        Some("no target regions")
+    } else {
+        None
+    }
+}
 
-    // Initialise counts of each trinucleotide to zero
-    let mut dinucleotide_counts = contextcounter::counts::CountsDi::default();
+/// Count the windows of a block that end in its new (not carried over) bases.
+/// `intervals` are the contig's target regions, in contig coordinates.
+fn count_block(
+    block: &Block<'_>,
+    intervals: Option<&[Interval]>,
+    flank_context: bool,
+    counts: &mut Counts,
+) {
+    let k = counts.k();
+    // First window start (in block coordinates) that ends in a new base
+    let from = block.carried.saturating_sub(k - 1);
 
-    info!("Counting dinucleotide contexts in [{}]", fasta.display());
+    let Some(intervals) = intervals else {
+        count_chunked(&block.seq[from..], None, counts);
+        return;
+    };
 
-    // Create FASTA file reader
-    let mut reader = File::open(fasta)
-        .map(BufReader::new)
-        .map(fasta::io::Reader::new)
-        .context("Failed to read dinucleotide counts")?;
+    let pad = flank_pad(k, flank_context);
+    let (first, end) = (block.start + from, block.start + block.seq.len());
+    let local: Vec<Interval> = intervals
+        [intervals.partition_point(|interval| interval.end.saturating_add(pad) <= first)..]
+        .iter()
+        .map(|interval| interval.flanked(pad, end))
+        .take_while(|flanked| flanked.start < end)
+        .filter_map(|flanked| {
+            let start = flanked.start.max(first);
+            (start < flanked.end).then(|| Interval {
+                start: start - block.start,
+                end: flanked.end - block.start,
+            })
+        })
+        .collect();
+    count_chunked(block.seq, Some(&local), counts);
+}
 
-    // Read each record (contains references to sequence names & info)
-    for result in reader.records() {
-        let record = result?;
-        let contig_name = std::str::from_utf8(record.definition().name().trim_ascii())?;
+/// Count a sequence's contexts in chunks of about [`CHUNK_SIZE`] bases, in parallel, then merge.
+/// Whole-sequence chunks overlap by k-1 bases so each window is counted exactly once;
+/// target regions are grouped into batches of whole intervals.
+fn count_chunked(seq_bytes: &[u8], intervals: Option<&[Interval]>, counts: &mut Counts) {
+    count_in_chunks(seq_bytes, intervals, counts, CHUNK_SIZE);
+}
 
-        // Check if contig should be skipped (in blacklist). Commonly used to exclude sex chromosomes from counts
-        if !skip.is_empty() && skip.contains(contig_name) {
-            info!("Contig: {} (skipped: in blacklist)", contig_name);
-            continue;
-        }
+fn count_in_chunks(
+    seq_bytes: &[u8],
+    intervals: Option<&[Interval]>,
+    counts: &mut Counts,
+    chunk_size: usize,
+) {
+    let k = counts.k();
+    let empty = counts.new_like();
 
-        // Check if contig should be skipped (not in whitelist).
-        if !include.is_empty() && !include.contains(contig_name) {
-            info!("Contig: {} (skipped: not in whitelist)", contig_name);
+    let chunk_counts: Vec<Counts> = match intervals {
+        Some(intervals) => {
+            let mut batches = Vec::new();
+            let (mut first, mut bases) = (0, 0);
+            for (i, interval) in intervals.iter().enumerate() {
+                bases += interval.len();
+                if bases >= chunk_size || i + 1 == intervals.len() {
+                    batches.push(&intervals[first..=i]);
+                    (first, bases) = (i + 1, 0);
+                }
+            }
+            batches
+                .into_par_iter()
+                .map(|batch| {
+                    let mut chunk = empty.clone();
+                    count_intervals(seq_bytes, batch, false, &mut chunk);
+                    chunk
+                })
+                .collect()
         }
-        // Otherwise, proceed with TNC counting
-        info!("Contig: {}", contig_name);
+        None => (0..seq_bytes.len().div_ceil(chunk_size))
+            .into_par_iter()
+            .map(|i| {
+                let start = i * chunk_size;
+                let end = (start + chunk_size + k - 1).min(seq_bytes.len());
+                let mut chunk = empty.clone();
+                count_windows(&seq_bytes[start..end], &mut chunk);
+                chunk
+            })
+            .collect(),
+    };
+
+    // Merge in chunk order, so fractional counts are summed in the same order for any number of
+    // threads
+    for chunk in &chunk_counts {
+        counts.merge(chunk);
+    }
+}
 
-        let seq_bytes = record.sequence().as_ref();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // Skip sequences shorter than the window size
-        if seq_bytes.len() < windowsize {
-            continue;
+    #[test]
+    fn test_fasta_matches_baseline_tables() {
+        let fasta = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/testfiles/test.fasta")).unwrap();
+
+        // Whole contigs, and blocks small enough that every window spans a boundary
+        for block_size in [usize::MAX, 4, 1] {
+            let mut trinucleotides = CountsTri::default();
+            let mut pentanucleotides = CountsPenta::default();
+            let mut dinucleotides = CountsDi::default();
+            count_contexts(
+                &mut ContigBlocks::new(fasta.as_slice(), block_size, 4),
+                &HashSet::new(),
+                &HashSet::new(),
+                None,
+                false,
+                &mut [
+                    &mut trinucleotides,
+                    &mut pentanucleotides,
+                    &mut dinucleotides,
+                ],
+                &mut ContigAnalyses::default(),
+            )
+            .unwrap();
+
+            assert_eq!(
+                trinucleotides.to_string(),
+                include_str!("../testfiles/test_trinucleotide.tsv")
+            );
+            assert_eq!(
+                pentanucleotides.to_string(),
+                include_str!("../testfiles/test_pentanucleotide.tsv")
+            );
+            assert_eq!(
+                dinucleotides.to_string(),
+                include_str!("../testfiles/test_dinucleotide.tsv")
+            );
         }
+    }
 
-        // Sliding window along fasta entry
-        for window in seq_bytes.windows(windowsize) {
-            // Fetch trinucleotide sequence
-            let dinucleotide = String::from_utf8(Vec::from(window)).unwrap();
-            dinucleotide_counts.increment(&dinucleotide);
+    /// Targets on testfiles/test.fasta (chr1 is 10 bases, chr2 13), one running past the chr1 end
+    fn test_targets() -> Regions {
+        let mut regions = Regions::default();
+        for (contig, start, end) in [
+            ("chr1", 2, 6),
+            ("chr1", 7, 50),
+            ("chr2", 0, 4),
+            ("chr2", 10, 13),
+        ] {
+            regions.push(contig, Interval { start, end });
         }
+        regions
     }
 
-    // Display count matrix
-    if print_counts {
-        println!("{}", dinucleotide_counts);
+    /// Count tri-, penta- and dinucleotides in targets of testfiles/test.fasta, read in blocks of
+    /// `block_size`, returning the printed tables and footprint
+    fn count_test_targets(block_size: usize, flank_context: bool) -> (Vec<String>, Footprint) {
+        let fasta = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/testfiles/test.fasta")).unwrap();
+        let mut tables: Vec<Counts> = [3, 5, 2].map(Counts::new).to_vec();
+        let (footprint, _) = count_contexts(
+            &mut ContigBlocks::new(fasta.as_slice(), block_size, 4),
+            &HashSet::new(),
+            &HashSet::new(),
+            Some(&test_targets()),
+            flank_context,
+            &mut tables.iter_mut().collect::<Vec<_>>(),
+            &mut ContigAnalyses::default(),
+        )
+        .unwrap();
+        let tables = tables
+            .iter()
+            .map(|table| table.unfolded().to_string())
+            .collect();
+        (tables, footprint)
+    }
+
+    #[test]
+    fn targeted_counts_span_block_boundaries() {
+        let contigs = [("chr1", &b"CAGTCGGGGT"[..]), ("chr2", b"ACACGACCCAAAT")];
+        for flank_context in [false, true] {
+            // Each target counted straight from the whole contig
+            let expected: Vec<String> = [3, 5, 2]
+                .map(|k| {
+                    let mut counts = Counts::new(k);
+                    for (contig, seq) in contigs {
+                        let targets = test_targets();
+                        let intervals: Vec<Interval> = targets
+                            .get(contig)
+                            .unwrap()
+                            .iter()
+                            .filter_map(|interval| interval.clip(seq.len()))
+                            .collect();
+                        count_intervals(seq, &intervals, flank_context, &mut counts);
+                    }
+                    counts.unfolded().to_string()
+                })
+                .to_vec();
+
+            for block_size in [usize::MAX, 1, 2, 3, 5] {
+                let (tables, _) = count_test_targets(block_size, flank_context);
+                assert_eq!(
+                    tables, expected,
+                    "block size {block_size}, flank {flank_context}"
+                );
+            }
+        }
     }
 
-    Ok(dinucleotide_counts)
+    #[test]
+    fn footprint_is_clipped_to_contig_ends() {
+        let (_, footprint) = count_test_targets(3, false);
+        // chr1:7-50 is clipped to the 10 bp contig
+        assert_eq!(
+            footprint.to_string(),
+            "contig\tintervals\tbases\nchr1\t2\t7\nchr2\t2\t7\ntotal\t4\t14\n"
+        );
+    }
+
+    #[test]
+    fn chunked_counts_do_not_depend_on_chunks_or_threads() {
+        let seq: Vec<u8> = b"ACGTTGCANNacgtCCCCAGGT".repeat(50);
+        let intervals = [
+            Interval { start: 3, end: 40 },
+            Interval {
+                start: 41,
+                end: 300,
+            },
+            Interval {
+                start: 500,
+                end: 1100,
+            },
+        ];
+        for intervals in [None, Some(&intervals[..])] {
+            for k in [2, 3, 5] {
+                // Counted in one pass over the whole sequence
+                let mut whole = Counts::new(k);
+                match intervals {
+                    Some(intervals) => count_intervals(&seq, intervals, false, &mut whole),
+                    None => count_windows(&seq, &mut whole),
+                }
+                let expected = whole.unfolded().to_string();
+
+                for chunk_size in [1, 7, 100] {
+                    for threads in [1, 4] {
+                        let pool = rayon::ThreadPoolBuilder::new()
+                            .num_threads(threads)
+                            .build()
+                            .unwrap();
+                        let mut counts = Counts::new(k);
+                        pool.install(|| count_in_chunks(&seq, intervals, &mut counts, chunk_size));
+                        assert_eq!(
+                            counts.unfolded().to_string(),
+                            expected,
+                            "k {k}, chunk size {chunk_size}, {threads} threads"
+                        );
+                    }
+                }
+            }
+        }
+    }
 }
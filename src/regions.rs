@@ -0,0 +1,667 @@
+use crate::{
+    counts::{ContextCounter, count_windows},
+    io::open_maybe_compressed,
+};
+use anyhow::{Context, bail};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// 0-based, half-open interval (BED convention)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Interval {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Interval {
+    /// Number of bases covered
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    /// Extend the interval by `pad` bases on either side, clipped to a contig of `length` bases
+    /// (see [`flank_pad`])
+    pub fn flanked(&self, pad: usize, length: usize) -> Interval {
+        Interval {
+            start: self.start.saturating_sub(pad),
+            end: self.end.saturating_add(pad).min(length),
+        }
+    }
+
+    /// Clip the interval to a contig of `length` bases. None if nothing remains
+    pub fn clip(&self, length: usize) -> Option<Interval> {
+        let clipped = Interval {
+            start: self.start.min(length),
+            end: self.end.min(length),
+        };
+        (!clipped.is_empty()).then_some(clipped)
+    }
+}
+
+/// Bases to extend each interval by on either side so that, with `flank_context`, every window of
+/// `k` bases centred on one of its bases fits (none without)
+pub fn flank_pad(k: usize, flank_context: bool) -> usize {
+    if flank_context { (k - 1) / 2 } else { 0 }
+}
+
+/// An interval covering a whole contig, whatever its length
+const WHOLE_CONTIG: [Interval; 1] = [Interval {
+    start: 0,
+    end: usize::MAX,
+}];
+
+/// Target regions (e.g. exome or gene panel capture targets) grouped by contig
+#[derive(Debug, Default, Clone)]
+pub struct Regions {
+    contigs: HashMap<String, Vec<Interval>>,
+    /// Whether contigs without intervals of their own are covered whole
+    whole_contigs: bool,
+    /// Bases removed from each contig by [`Regions::subtract`]
+    removed: HashMap<String, Vec<Interval>>,
+}
+
+impl Regions {
+    /// Read regions from a BED file. Only the first three columns are used.
+    /// Blank lines, comments and `track`/`browser` header lines are ignored.
+    pub fn from_bed(path: &Path) -> Result<Self, anyhow::Error> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open BED file: {}", path.display()))?;
+
+        let mut regions = Regions::default();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line =
+                line.with_context(|| format!("Failed to read BED file: {}", path.display()))?;
+            if is_bed_header(&line) {
+                continue;
+            }
+
+            let (contig, interval) = parse_bed_line(&line)
+                .with_context(|| format!("Invalid BED line {} in {}", i + 1, path.display()))?;
+            regions.push(contig, interval);
+        }
+
+        Ok(regions)
+    }
+
+    /// Read callable regions from a per-base depth track: a mosdepth `per-base.bed.gz` or a
+    /// bedGraph (plain or gzip compressed), with the depth in column 4. Runs of bases with a depth
+    /// of at least `min_depth` are kept, merging runs that abut.
+    pub fn from_depth(path: &Path, min_depth: f64) -> Result<Self, anyhow::Error> {
+        let (reader, _) = open_maybe_compressed(path, 1)
+            .with_context(|| format!("Failed to open depth file: {}", path.display()))?;
+
+        let mut regions = Regions::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line =
+                line.with_context(|| format!("Failed to read depth file: {}", path.display()))?;
+            if is_bed_header(&line) {
+                continue;
+            }
+
+            let parsed = parse_bed_line(&line).and_then(|(contig, interval)| {
+                let Some(depth) = line.split('\t').nth(3) else {
+                    bail!("expected a 4th column with the depth");
+                };
+                let depth: f64 = depth
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("depth is not a number: '{depth}'"))?;
+                Ok((contig, interval, depth))
+            });
+            let (contig, interval, depth) =
+                parsed.with_context(|| format!("Invalid line {} in {}", i + 1, path.display()))?;
+            if depth < min_depth || interval.is_empty() {
+                continue;
+            }
+
+            // Extend the previous run if this one continues it
+            let last = regions
+                .contigs
+                .get_mut(contig)
+                .and_then(|runs| runs.last_mut());
+            match last {
+                Some(last) if last.end == interval.start => last.end = interval.end,
+                _ => regions.push(contig, interval),
+            }
+        }
+
+        regions.merge();
+        Ok(regions)
+    }
+
+    /// Regions covering every contig whole, to subtract excluded regions from
+    /// (interval ends are clipped to the contig length once it is known)
+    pub fn whole_genome() -> Self {
+        Self {
+            whole_contigs: true,
+            ..Self::default()
+        }
+    }
+
+    /// Add an interval to a contig
+    pub fn push(&mut self, contig: &str, interval: Interval) {
+        self.contigs
+            .entry(contig.to_string())
+            .or_default()
+            .push(interval);
+    }
+
+    /// Extend every interval by `bases` on each side (clipped at 0; ends are clipped to the
+    /// contig length once it is known)
+    pub fn pad(&mut self, bases: usize) {
+        for interval in self.contigs.values_mut().flatten() {
+            interval.start = interval.start.saturating_sub(bases);
+            interval.end = interval.end.saturating_add(bases);
+        }
+    }
+
+    /// Sort intervals and merge any that overlap or abut, so no base is covered twice
+    pub fn merge(&mut self) {
+        for intervals in self.contigs.values_mut() {
+            intervals.retain(|interval| !interval.is_empty());
+            intervals.sort_unstable();
+
+            let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+            for interval in intervals.drain(..) {
+                match merged.last_mut() {
+                    Some(last) if interval.start <= last.end => {
+                        last.end = last.end.max(interval.end)
+                    }
+                    _ => merged.push(interval),
+                }
+            }
+            *intervals = merged;
+        }
+    }
+
+    /// Remove the bases of `excluded` (e.g. a blacklist) from the regions. Both must be merged.
+    /// The bases removed are kept (see [`Regions::removed`]) to report once contig lengths are known.
+    pub fn subtract(&mut self, excluded: &Regions) {
+        for (contig, excluded) in &excluded.contigs {
+            let Some(intervals) = self.get(contig) else {
+                continue;
+            };
+            let removed = intersect_intervals(intervals, excluded);
+            if removed.is_empty() {
+                continue;
+            }
+            let remaining = subtract_intervals(intervals, excluded);
+            self.contigs.insert(contig.clone(), remaining);
+            self.removed.insert(contig.clone(), removed);
+        }
+    }
+
+    /// Bases removed from a contig by [`Regions::subtract`] (interval ends may lie past the
+    /// contig end)
+    pub fn removed(&self, contig: &str) -> &[Interval] {
+        self.removed.get(contig).map_or(&[], Vec::as_slice)
+    }
+
+    /// Regions covered by both these regions and `other`. Both must be merged.
+    pub fn intersect(&self, other: &Regions) -> Regions {
+        let mut contigs: Vec<&String> = self.contigs.keys().collect();
+        if self.whole_contigs {
+            contigs.extend(other.contigs.keys());
+        }
+
+        let mut intersection = Regions::default();
+        for contig in contigs {
+            if let (Some(intervals), Some(others)) = (self.get(contig), other.get(contig)) {
+                let overlaps = intersect_intervals(intervals, others);
+                if !overlaps.is_empty() {
+                    intersection.contigs.insert(contig.clone(), overlaps);
+                }
+            }
+        }
+        intersection.whole_contigs = self.whole_contigs && other.whole_contigs;
+        intersection
+    }
+
+    /// Intervals on a contig (None if the contig has no regions)
+    pub fn get(&self, contig: &str) -> Option<&[Interval]> {
+        match self.contigs.get(contig) {
+            Some(intervals) => Some(intervals),
+            None => self.whole_contigs.then_some(&WHOLE_CONTIG[..]),
+        }
+    }
+
+    /// Number of intervals across all contigs
+    pub fn len(&self) -> usize {
+        self.contigs.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Remove the bases of `excluded` from sorted, non-overlapping `intervals`
+pub fn subtract_intervals(intervals: &[Interval], excluded: &[Interval]) -> Vec<Interval> {
+    let mut remaining = Vec::with_capacity(intervals.len());
+    for &interval in intervals {
+        let mut start = interval.start;
+        let first = excluded.partition_point(|exclusion| exclusion.end <= start);
+        for exclusion in excluded[first..]
+            .iter()
+            .take_while(|exclusion| exclusion.start < interval.end)
+        {
+            if exclusion.start > start {
+                remaining.push(Interval {
+                    start,
+                    end: exclusion.start,
+                });
+            }
+            start = start.max(exclusion.end);
+        }
+        if start < interval.end {
+            remaining.push(Interval {
+                start,
+                end: interval.end,
+            });
+        }
+    }
+    remaining
+}
+
+/// Bases covered by both sorted, non-overlapping `intervals` and `others`
+pub fn intersect_intervals(intervals: &[Interval], others: &[Interval]) -> Vec<Interval> {
+    let mut overlaps = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < intervals.len() && j < others.len() {
+        let overlap = Interval {
+            start: intervals[i].start.max(others[j].start),
+            end: intervals[i].end.min(others[j].end),
+        };
+        if !overlap.is_empty() {
+            overlaps.push(overlap);
+        }
+        // Move past whichever interval ends first
+        if intervals[i].end <= others[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    overlaps
+}
+
+/// Count the contexts within each interval of a sequence.
+/// By default only windows lying entirely inside an interval are counted. With `flank_context`,
+/// windows whose central base(s) are inside an interval are counted too, using bases outside it
+/// as context. Intervals should be merged and clipped to the sequence length.
+pub fn count_intervals(
+    seq_bytes: &[u8],
+    intervals: &[Interval],
+    flank_context: bool,
+    counter: &mut dyn ContextCounter,
+) {
+    let pad = flank_pad(counter.window_size(), flank_context);
+    for interval in intervals {
+        let flanked = interval.flanked(pad, seq_bytes.len());
+        if !flanked.is_empty() {
+            count_windows(&seq_bytes[flanked.start..flanked.end], counter);
+        }
+    }
+}
+
+/// Blank, comment and `track`/`browser` lines carry no intervals
+pub(crate) fn is_bed_header(line: &str) -> bool {
+    line.trim().is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+}
+
+/// Parse the chrom, start and end columns of a BED line
+pub(crate) fn parse_bed_line(line: &str) -> Result<(&str, Interval), anyhow::Error> {
+    let mut fields = line.split('\t');
+    let (Some(contig), Some(start), Some(end)) = (fields.next(), fields.next(), fields.next())
+    else {
+        bail!("expected at least 3 tab-separated columns (chrom, start, end)");
+    };
+
+    let start: usize = start
+        .trim()
+        .parse()
+        .with_context(|| format!("start is not a non-negative integer: '{start}'"))?;
+    let end: usize = end
+        .trim()
+        .parse()
+        .with_context(|| format!("end is not a non-negative integer: '{end}'"))?;
+    if end < start {
+        bail!("end ({end}) is before start ({start})");
+    }
+
+    Ok((contig, Interval { start, end }))
+}
+
+/// Size of the counted target footprint on each contig, after padding, merging and
+/// clipping regions to contig lengths
+#[derive(Debug, Default, Clone)]
+pub struct Footprint {
+    contigs: Vec<(String, usize, usize)>,
+    excluded: ExcludedBases,
+}
+
+impl Footprint {
+    /// Record the final intervals counted on a contig
+    pub fn add(&mut self, contig: &str, intervals: &[Interval]) {
+        let bases = intervals.iter().map(Interval::len).sum();
+        self.contigs
+            .push((contig.to_string(), intervals.len(), bases));
+    }
+
+    /// Record the bases of a counted contig removed by excluded regions, clipped to its length
+    pub fn add_excluded(&mut self, contig: &str, removed: &[Interval], length: usize) {
+        let bases: usize = removed
+            .iter()
+            .filter_map(|interval| interval.clip(length))
+            .map(|interval| interval.len())
+            .sum();
+        if bases > 0 {
+            self.excluded.contigs.push((contig.to_string(), bases));
+        }
+    }
+
+    /// Bases removed by excluded regions from each counted contig
+    pub fn excluded(&self) -> &ExcludedBases {
+        &self.excluded
+    }
+
+    /// Total bases in the footprint
+    pub fn total(&self) -> usize {
+        self.contigs.iter().map(|&(_, _, bases)| bases).sum()
+    }
+
+    /// Core printer: writes a per-contig table (plus total) with the given delimiter
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "contig{d}intervals{d}bases", d = delim)?;
+
+        for (contig, intervals, bases) in &self.contigs {
+            writeln!(f, "{contig}{d}{intervals}{d}{bases}", d = delim)?;
+        }
+        let intervals: usize = self.contigs.iter().map(|&(_, n, _)| n).sum();
+        writeln!(
+            f,
+            "total{d}{intervals}{d}{bases}",
+            d = delim,
+            bases = self.total()
+        )
+    }
+}
+
+impl fmt::Display for Footprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+/// Number of bases removed by excluded regions from each counted contig that lost any, after
+/// clipping regions to contig lengths
+#[derive(Debug, Default, Clone)]
+pub struct ExcludedBases {
+    contigs: Vec<(String, usize)>,
+}
+
+impl ExcludedBases {
+    /// Contigs that lost bases, with the number of bases removed, in the order they were counted
+    pub fn contigs(&self) -> &[(String, usize)] {
+        &self.contigs
+    }
+
+    /// Total bases removed
+    pub fn total(&self) -> usize {
+        self.contigs.iter().map(|(_, bases)| bases).sum()
+    }
+
+    /// Core printer: writes a per-contig table (plus total) with the given delimiter
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "contig{d}bases", d = delim)?;
+
+        for (contig, bases) in &self.contigs {
+            writeln!(f, "{contig}{d}{bases}", d = delim)?;
+        }
+        writeln!(f, "total{d}{bases}", d = delim, bases = self.total())
+    }
+}
+
+impl fmt::Display for ExcludedBases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(start: usize, end: usize) -> Interval {
+        Interval { start, end }
+    }
+
+    #[test]
+    fn bed_lines() {
+        for header in [
+            "",
+            "  ",
+            "# comment",
+            "track name=targets",
+            "browser position chr1",
+        ] {
+            assert!(is_bed_header(header), "{header:?}");
+        }
+        assert!(!is_bed_header("chr1\t0\t10"));
+
+        let (contig, parsed) = parse_bed_line("chr1\t5\t10\tname\t0\t+").unwrap();
+        assert_eq!((contig, parsed), ("chr1", interval(5, 10)));
+        for invalid in ["chr1\t10\t5", "chr1\t5", "chr1\t-1\t5", "chr1 5 10"] {
+            assert!(parse_bed_line(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn bed_file() {
+        let path = std::env::temp_dir().join(format!("contextcounter-{}.bed", std::process::id()));
+        std::fs::write(&path, "track name=t\nchr2\t5\t10\n\nchr1\t0\t3\tgene\n").unwrap();
+        let regions = Regions::from_bed(&path);
+        std::fs::write(&path, "chr1\t0\t3\nchr1\t9\t8\n").unwrap();
+        let error = Regions::from_bed(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        let regions = regions.unwrap();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions.get("chr1").unwrap(), [interval(0, 3)]);
+        assert_eq!(regions.get("chr2").unwrap(), [interval(5, 10)]);
+        assert!(regions.get("chr3").is_none());
+        assert!(
+            format!("{error:#}").contains("Invalid BED line 2"),
+            "{error:#}"
+        );
+    }
+
+    #[test]
+    fn depth_tracks() {
+        let track = "track type=bedGraph\nchr1\t0\t10\t5\nchr1\t10\t20\t12\nchr1\t20\t25\t30\n\
+                     chr1\t25\t30\t3\nchr1\t30\t40\t20.5\nchr2\t0\t5\t0\nchr2\t5\t8\t10\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, track.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "contextcounter-{}.per-base.bed.gz",
+            std::process::id()
+        ));
+        let read = |contents: &[u8], min_depth| {
+            std::fs::write(&path, contents).unwrap();
+            Regions::from_depth(&path, min_depth)
+        };
+        let plain = read(track.as_bytes(), 10.0);
+        let compressed = read(&gzipped, 10.0);
+        let deeper = read(track.as_bytes(), 20.0);
+        let no_depth = read(b"chr1\t0\t10\t5\nchr1\t10\t20\n", 10.0);
+        let not_a_number = read(b"chr1\t0\t10\thigh\n", 10.0);
+        std::fs::remove_file(&path).unwrap();
+
+        // Runs of at least the minimum depth, abutting runs merged
+        for regions in [plain.unwrap(), compressed.unwrap()] {
+            assert_eq!(
+                regions.get("chr1").unwrap(),
+                [interval(10, 25), interval(30, 40)]
+            );
+            assert_eq!(regions.get("chr2").unwrap(), [interval(5, 8)]);
+        }
+        let deeper = deeper.unwrap();
+        assert_eq!(
+            deeper.get("chr1").unwrap(),
+            [interval(20, 25), interval(30, 40)]
+        );
+        assert!(deeper.get("chr2").is_none());
+
+        let error = format!("{:#}", no_depth.unwrap_err());
+        assert!(
+            error.contains("Invalid line 2") && error.contains("4th column"),
+            "{error}"
+        );
+        let error = format!("{:#}", not_a_number.unwrap_err());
+        assert!(error.contains("depth is not a number: 'high'"), "{error}");
+    }
+
+    #[test]
+    fn pad_and_merge() {
+        let mut regions = Regions::default();
+        for (start, end) in [
+            (30, 40),
+            (1, 5),
+            (10, 20),
+            (20, 25),
+            (12, 15),
+            (50, 50),
+            (60, 70),
+        ] {
+            regions.push("chr1", interval(start, end));
+        }
+        regions.merge();
+        // Overlapping and abutting intervals are merged, empty ones dropped
+        assert_eq!(
+            regions.get("chr1").unwrap(),
+            [
+                interval(1, 5),
+                interval(10, 25),
+                interval(30, 40),
+                interval(60, 70)
+            ]
+        );
+
+        // Padding clips at 0 and joins intervals it makes overlap or abut
+        regions.pad(3);
+        regions.merge();
+        assert_eq!(
+            regions.get("chr1").unwrap(),
+            [interval(0, 43), interval(57, 73)]
+        );
+    }
+
+    #[test]
+    fn clip_to_contig_end() {
+        assert_eq!(interval(5, 20).clip(10), Some(interval(5, 10)));
+        assert_eq!(interval(5, 10).clip(10), Some(interval(5, 10)));
+        assert_eq!(interval(10, 20).clip(10), None);
+
+        let mut footprint = Footprint::default();
+        footprint.add("chr1", &[interval(0, 4), interval(5, 10)]);
+        footprint.add("chr2", &[interval(2, 3)]);
+        assert_eq!(footprint.total(), 10);
+        assert_eq!(
+            footprint.to_string(),
+            "contig\tintervals\tbases\nchr1\t2\t9\nchr2\t1\t1\ntotal\t3\t10\n"
+        );
+    }
+
+    #[test]
+    fn count_windows_inside_intervals() {
+        let seq = b"ACGTACGTAC";
+        let count = |intervals: &[Interval], flank_context| {
+            let mut counts = crate::counts::CountsTri::default();
+            count_intervals(seq, intervals, flank_context, &mut counts);
+            counts
+        };
+
+        // Only GTA and TAC (both reported as TAC) lie inside 2-6
+        let counts = count(&[interval(2, 6)], false);
+        assert_eq!(counts.total(true), 2);
+        assert_eq!(counts.count("TAC"), Some(2));
+
+        // With flank context every base of 2-6 is the centre of a window: CGT, GTA, TAC and ACG
+        let counts = count(&[interval(2, 6)], true);
+        assert_eq!(counts.total(true), 4);
+        assert_eq!(counts.count("ACG"), Some(2));
+
+        // Windows cannot extend past the sequence, and intervals too short for a window add none
+        assert_eq!(
+            count(&[interval(0, 2), interval(8, 10)], true).total(true),
+            2
+        );
+        assert_eq!(
+            count(&[interval(0, 2), interval(8, 10)], false).total(true),
+            0
+        );
+    }
+
+    #[test]
+    fn excluded_bases_are_clipped_to_counted_contigs() {
+        let mut excluded = Regions::default();
+        excluded.push("chr1", interval(10, 20));
+        excluded.push("chr1", interval(90, 500));
+        excluded.push("chrZ", interval(0, 100));
+
+        let mut regions = Regions::whole_genome();
+        regions.subtract(&excluded);
+        assert_eq!(
+            regions.removed("chr1"),
+            [interval(10, 20), interval(90, 500)]
+        );
+
+        // Only the bases inside a 100 bp chr1 are reported; chrZ is never counted
+        let mut footprint = Footprint::default();
+        let length = 100;
+        footprint.add_excluded("chr1", regions.removed("chr1"), length);
+        assert_eq!(footprint.excluded().contigs(), [("chr1".to_string(), 20)]);
+        assert_eq!(
+            footprint.excluded().to_string(),
+            "contig\tbases\nchr1\t20\ntotal\t20\n"
+        );
+    }
+
+    #[test]
+    fn subtract_from_targets() {
+        let mut regions = Regions::default();
+        regions.push("chr1", interval(0, 50));
+        regions.push("chr1", interval(60, 80));
+        let mut excluded = Regions::default();
+        excluded.push("chr1", interval(40, 70));
+
+        regions.subtract(&excluded);
+        assert_eq!(
+            regions.get("chr1").unwrap(),
+            [interval(0, 40), interval(70, 80)]
+        );
+        assert_eq!(
+            regions.removed("chr1"),
+            [interval(40, 50), interval(60, 70)]
+        );
+        assert!(regions.get("chr2").is_none());
+    }
+}
@@ -0,0 +1,573 @@
+use crate::{
+    annotation::Strand,
+    counts::{Counts, CountsTri},
+    genes::Gene,
+    regions::{Interval, flank_pad, is_bed_header, parse_bed_line},
+};
+use anyhow::{Context, bail};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// Which reference strands an annotated segment of a contig covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// Only features on the + strand
+    Forward,
+    /// Only features on the - strand
+    Reverse,
+    /// Features on both strands
+    Both,
+    /// No features
+    Neither,
+}
+
+/// Non-overlapping segments of constant [`Orientation`], grouped by contig
+#[derive(Debug, Default, Clone)]
+pub struct StrandSegments {
+    contigs: HashMap<String, Vec<(Interval, Orientation)>>,
+}
+
+impl StrandSegments {
+    /// Build segments from (possibly overlapping) stranded intervals.
+    /// Intervals with an unknown strand are ignored. Only covered segments are stored;
+    /// anything between them is [`Orientation::Neither`].
+    pub fn from_stranded_intervals<'a>(
+        intervals: impl IntoIterator<Item = (&'a str, Interval, Strand)>,
+    ) -> Self {
+        // Sweep over start (+1) and end (-1) events for each strand
+        let mut events: HashMap<&str, Vec<(usize, i64, i64)>> = HashMap::new();
+        for (contig, interval, strand) in intervals {
+            let (forward, reverse) = match strand {
+                Strand::Forward => (1, 0),
+                Strand::Reverse => (0, 1),
+                Strand::Unknown => continue,
+            };
+            if interval.is_empty() {
+                continue;
+            }
+            let contig_events = events.entry(contig).or_default();
+            contig_events.push((interval.start, forward, reverse));
+            contig_events.push((interval.end, -forward, -reverse));
+        }
+
+        let mut contigs = HashMap::new();
+        for (contig, mut contig_events) in events {
+            contig_events.sort_unstable_by_key(|&(position, _, _)| position);
+
+            let mut segments: Vec<(Interval, Orientation)> = Vec::new();
+            let (mut forward, mut reverse) = (0, 0);
+            let mut previous = 0;
+            for (position, forward_change, reverse_change) in contig_events {
+                let orientation = match (forward > 0, reverse > 0) {
+                    (true, true) => Orientation::Both,
+                    (true, false) => Orientation::Forward,
+                    (false, true) => Orientation::Reverse,
+                    (false, false) => Orientation::Neither,
+                };
+                if position > previous && orientation != Orientation::Neither {
+                    match segments.last_mut() {
+                        // Extend the previous segment if it abuts with the same orientation
+                        Some((last, last_orientation))
+                            if last.end == previous && *last_orientation == orientation =>
+                        {
+                            last.end = position
+                        }
+                        _ => segments.push((
+                            Interval {
+                                start: previous,
+                                end: position,
+                            },
+                            orientation,
+                        )),
+                    }
+                }
+                forward += forward_change;
+                reverse += reverse_change;
+                previous = position;
+            }
+            contigs.insert(contig.to_string(), segments);
+        }
+
+        Self { contigs }
+    }
+
+    /// Segments from the full span (first to last base) of each gene (see
+    /// [`crate::genes::gene_spans`])
+    pub fn from_gene_spans(genes: &[Gene]) -> Self {
+        Self::from_stranded_intervals(genes.iter().filter_map(|gene| {
+            let span = Interval {
+                start: gene.intervals.first()?.start,
+                end: gene.intervals.last()?.end,
+            };
+            Some((gene.contig.as_str(), span, gene.strand))
+        }))
+    }
+
+    /// Split `range` of a contig into consecutive pieces of constant orientation
+    pub fn split(&self, contig: &str, range: Interval) -> Vec<(Interval, Orientation)> {
+        let mut pieces = Vec::new();
+        let mut position = range.start;
+        let segments = self
+            .contigs
+            .get(contig)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        // First segment that ends after the start of the range
+        let first = segments.partition_point(|(segment, _)| segment.end <= range.start);
+        for &(segment, orientation) in &segments[first..] {
+            if segment.start >= range.end {
+                break;
+            }
+            if segment.start > position {
+                pieces.push((
+                    Interval {
+                        start: position,
+                        end: segment.start,
+                    },
+                    Orientation::Neither,
+                ));
+            }
+            let start = segment.start.max(position);
+            let end = segment.end.min(range.end);
+            pieces.push((Interval { start, end }, orientation));
+            position = end;
+        }
+        if position < range.end {
+            pieces.push((
+                Interval {
+                    start: position,
+                    end: range.end,
+                },
+                Orientation::Neither,
+            ));
+        }
+        pieces
+    }
+}
+
+/// Ranges of positions whose (centred) trinucleotide should be counted on a contig.
+/// If `intervals` is supplied only windows inside those regions are counted
+/// (or, with `flank_context`, windows whose central base is inside a region).
+fn trinucleotide_centres(
+    seq_len: usize,
+    intervals: Option<&[Interval]>,
+    flank_context: bool,
+) -> Vec<Interval> {
+    let whole_contig = [Interval {
+        start: 0,
+        end: seq_len,
+    }];
+
+    let pad = flank_pad(3, flank_context);
+    intervals
+        .unwrap_or(&whole_contig)
+        .iter()
+        .map(|interval| {
+            // Centres of the windows that fit in the (flanked) interval
+            let flanked = interval.flanked(pad, seq_len);
+            Interval {
+                start: flanked.start + 1,
+                end: flanked.end.saturating_sub(1),
+            }
+        })
+        .filter(|centres| !centres.is_empty())
+        .collect()
+}
+
+/// Trinucleotide counts split by transcriptional strand (as in SigProfiler's SBS288/SBS384).
+/// The strand is that of the pyrimidine in the reported (pyrimidine centered) context:
+/// - T (transcribed): the pyrimidine lies on the template strand of a gene
+/// - U (untranscribed): the pyrimidine lies on the coding strand of a gene
+/// - B (bidirectional): genes on both strands overlap the position
+/// - N (non-transcribed): no gene overlaps the position
+#[derive(Debug, Clone)]
+pub struct TranscriptionalStrandCounts {
+    segments: StrandSegments,
+    transcribed: CountsTri,
+    untranscribed: CountsTri,
+    bidirectional: CountsTri,
+    non_transcribed: CountsTri,
+}
+
+impl TranscriptionalStrandCounts {
+    pub fn new(segments: StrandSegments) -> Self {
+        Self {
+            segments,
+            transcribed: CountsTri::default(),
+            untranscribed: CountsTri::default(),
+            bidirectional: CountsTri::default(),
+            non_transcribed: CountsTri::default(),
+        }
+    }
+
+    /// The transcribed, untranscribed, bidirectional and non-transcribed tables, e.g. to set
+    /// their soft-mask mode
+    pub fn tables_mut(&mut self) -> [&mut Counts; 4] {
+        [
+            &mut self.transcribed,
+            &mut self.untranscribed,
+            &mut self.bidirectional,
+            &mut self.non_transcribed,
+        ]
+    }
+
+    /// Count contexts on a contig. If `intervals` is supplied only windows inside those regions
+    /// are counted (or, with `flank_context`, windows whose central base is inside a region).
+    pub fn count_contig(
+        &mut self,
+        contig: &str,
+        seq_bytes: &[u8],
+        intervals: Option<&[Interval]>,
+        flank_context: bool,
+    ) {
+        for centres in trinucleotide_centres(seq_bytes.len(), intervals, flank_context) {
+            for (piece, orientation) in self.segments.split(contig, centres) {
+                for centre in piece.start..piece.end {
+                    let window = &seq_bytes[centre - 1..centre + 2];
+                    let purine_centred = matches!(window[1], b'A' | b'G' | b'a' | b'g');
+                    let counts = match (orientation, purine_centred) {
+                        // The + strand is coding: a purine here means the pyrimidine is on the template strand
+                        (Orientation::Forward, true) | (Orientation::Reverse, false) => {
+                            &mut self.transcribed
+                        }
+                        (Orientation::Forward, false) | (Orientation::Reverse, true) => {
+                            &mut self.untranscribed
+                        }
+                        (Orientation::Both, _) => &mut self.bidirectional,
+                        (Orientation::Neither, _) => &mut self.non_transcribed,
+                    };
+                    counts.increment_bytes(window);
+                }
+            }
+        }
+    }
+
+    /// Core printer: writes a two-column table with strand-prefixed contexts (e.g. `T:ACA`)
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "context{d}count", d = delim)?;
+
+        let categories = [
+            ("T", &self.transcribed),
+            ("U", &self.untranscribed),
+            ("B", &self.bidirectional),
+            ("N", &self.non_transcribed),
+        ];
+        for (category, counts) in categories {
+            for (ctx, cnt) in counts.weighted_contexts() {
+                writeln!(f, "{category}:{ctx}{d}{cnt}", d = delim)?;
+            }
+        }
+        let other: u64 = categories.iter().map(|(_, counts)| counts.other()).sum();
+        writeln!(f, "other{d}{other}", d = delim)
+    }
+}
+
+impl fmt::Display for TranscriptionalStrandCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+/// Trinucleotide counts split by replication strand and replication timing bin.
+///
+/// Regions come from a BED file whose 4th column gives the direction replication forks travel:
+/// `+` / `right` / `R` towards increasing coordinates, `-` / `left` / `L` towards decreasing ones.
+/// An optional 5th column assigns each region to a timing bin (e.g. a Repli-seq quantile).
+///
+/// A rightward fork synthesises the leading strand on the - strand template, so the + strand is
+/// the lagging strand template (and vice versa for leftward forks). Each context is assigned to
+/// the strand template carrying its pyrimidine. Positions outside the BED, or where regions with
+/// opposite directions overlap, are not counted.
+#[derive(Debug, Clone)]
+pub struct ReplicationStrandCounts {
+    /// (timing bin, fork direction segments, leading, lagging) in order of first appearance
+    bins: Vec<(String, StrandSegments, CountsTri, CountsTri)>,
+}
+
+impl ReplicationStrandCounts {
+    /// Read fork directions and (optional) timing bins from a BED file
+    pub fn from_bed(path: &Path) -> Result<Self, anyhow::Error> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open BED file: {}", path.display()))?;
+
+        // (timing bin, [(contig, interval, fork direction)])
+        type BinRegions = (String, Vec<(String, Interval, Strand)>);
+        let mut bin_regions: Vec<BinRegions> = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line =
+                line.with_context(|| format!("Failed to read BED file: {}", path.display()))?;
+            if is_bed_header(&line) {
+                continue;
+            }
+
+            let parsed = parse_bed_line(&line).and_then(|(contig, interval)| {
+                let mut extra = line.split('\t').skip(3);
+                let direction = match extra.next().map(str::trim) {
+                    Some("+" | "right" | "R") => Strand::Forward,
+                    Some("-" | "left" | "L") => Strand::Reverse,
+                    Some(other) => bail!("unrecognised fork direction '{other}'"),
+                    None => bail!("expected a 4th column with the fork direction"),
+                };
+                let bin = extra.next().map(str::trim).unwrap_or("all").to_string();
+                Ok((contig.to_string(), interval, direction, bin))
+            });
+            let (contig, interval, direction, bin) = parsed
+                .with_context(|| format!("Invalid BED line {} in {}", i + 1, path.display()))?;
+
+            match bin_regions.iter_mut().find(|(name, _)| *name == bin) {
+                Some((_, regions)) => regions.push((contig, interval, direction)),
+                None => bin_regions.push((bin, vec![(contig, interval, direction)])),
+            }
+        }
+
+        let bins = bin_regions
+            .into_iter()
+            .map(|(bin, regions)| {
+                let segments =
+                    StrandSegments::from_stranded_intervals(regions.iter().map(
+                        |(contig, interval, direction)| (contig.as_str(), *interval, *direction),
+                    ));
+                (bin, segments, CountsTri::default(), CountsTri::default())
+            })
+            .collect();
+
+        Ok(Self { bins })
+    }
+
+    /// The leading and lagging strand tables of every timing bin, e.g. to set their soft-mask mode
+    pub fn tables_mut(&mut self) -> impl Iterator<Item = &mut Counts> {
+        self.bins
+            .iter_mut()
+            .flat_map(|(_, _, leading, lagging)| [&mut **leading, &mut **lagging])
+    }
+
+    /// Number of timing bins
+    pub fn bins(&self) -> usize {
+        self.bins.len()
+    }
+
+    /// Count contexts on a contig. If `intervals` is supplied only windows inside those regions
+    /// are counted (or, with `flank_context`, windows whose central base is inside a region).
+    pub fn count_contig(
+        &mut self,
+        contig: &str,
+        seq_bytes: &[u8],
+        intervals: Option<&[Interval]>,
+        flank_context: bool,
+    ) {
+        for centres in trinucleotide_centres(seq_bytes.len(), intervals, flank_context) {
+            for (_, segments, leading, lagging) in &mut self.bins {
+                for (piece, orientation) in segments.split(contig, centres) {
+                    for centre in piece.start..piece.end {
+                        let window = &seq_bytes[centre - 1..centre + 2];
+                        let purine_centred = matches!(window[1], b'A' | b'G' | b'a' | b'g');
+                        let counts = match (orientation, purine_centred) {
+                            // Rightward fork: the + strand is the lagging strand template, so a
+                            // purine here means the pyrimidine is on the leading strand template
+                            (Orientation::Forward, true) | (Orientation::Reverse, false) => {
+                                &mut *leading
+                            }
+                            (Orientation::Forward, false) | (Orientation::Reverse, true) => {
+                                &mut *lagging
+                            }
+                            (Orientation::Both | Orientation::Neither, _) => continue,
+                        };
+                        counts.increment_bytes(window);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Core printer: writes a long-format (bin, strand, context, count) table
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "bin{d}strand{d}context{d}count", d = delim)?;
+
+        for (bin, _, leading, lagging) in &self.bins {
+            for (strand, counts) in [("leading", leading), ("lagging", lagging)] {
+                for (ctx, cnt) in counts.weighted_contexts() {
+                    writeln!(f, "{bin}{d}{strand}{d}{ctx}{d}{cnt}", d = delim)?;
+                }
+                writeln!(
+                    f,
+                    "{bin}{d}{strand}{d}other{d}{cnt}",
+                    d = delim,
+                    cnt = counts.other()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReplicationStrandCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{annotation::Feature, genes::gene_spans};
+
+    fn feature(
+        feature_type: &str,
+        gene_id: &str,
+        start: usize,
+        end: usize,
+        strand: Strand,
+    ) -> Feature {
+        Feature {
+            contig: "chr1".to_string(),
+            interval: Interval { start, end },
+            strand,
+            feature_type: feature_type.to_string(),
+            gene_id: gene_id.to_string(),
+            gene_name: gene_id.to_string(),
+            gene_biotype: String::new(),
+        }
+    }
+
+    /// A + gene over 2-8 and a - gene over 6-14 of a 20 bp chr1, with the GFF3 records that
+    /// are not genes
+    fn features() -> Vec<Feature> {
+        vec![
+            feature("region", "NC_000001.11:1..20", 0, 20, Strand::Forward),
+            feature("gene", "gene:G1", 2, 8, Strand::Forward),
+            feature("CDS", "gene:G1", 3, 5, Strand::Forward),
+            feature("biological_region", "", 0, 12, Strand::Forward),
+            feature("ncRNA_gene", "gene:G2", 6, 14, Strand::Reverse),
+            feature("exon", "gene:G2", 6, 7, Strand::Reverse),
+        ]
+    }
+
+    /// Count strand tables for a homopolymer contig
+    fn count(base: u8) -> String {
+        let genes = gene_spans(&features());
+        let mut counts = TranscriptionalStrandCounts::new(StrandSegments::from_gene_spans(&genes));
+        counts.count_contig("chr1", &[base; 20], None, false);
+        counts
+            .to_string()
+            .lines()
+            .filter(|row| !row.ends_with("\t0"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn gene_spans_come_from_gene_records() {
+        let genes = gene_spans(&features());
+        let spans: Vec<(&str, Vec<Interval>)> = genes
+            .iter()
+            .map(|gene| (gene.id.as_str(), gene.intervals.clone()))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("gene:G1", vec![Interval { start: 2, end: 8 }]),
+                ("gene:G2", vec![Interval { start: 6, end: 14 }]),
+            ]
+        );
+
+        // Without gene records, genes span all of their features
+        let genes = gene_spans(&features()[2..3]);
+        assert_eq!(genes[0].intervals, [Interval { start: 3, end: 5 }]);
+    }
+
+    #[test]
+    fn transcribed_and_untranscribed_strands() {
+        // Centres 2-5 lie in the + gene only, 6-7 in both genes, 8-13 in the - gene only and
+        // 1 and 14-18 in neither. A T on the + (coding) strand is untranscribed
+        assert_eq!(
+            count(b'T'),
+            "context\tcount\nT:TTT\t6\nU:TTT\t4\nB:TTT\t2\nN:TTT\t6"
+        );
+        // An A on the + strand means the pyrimidine (T) is on the - (template) strand
+        assert_eq!(
+            count(b'A'),
+            "context\tcount\nT:TTT\t4\nU:TTT\t6\nB:TTT\t2\nN:TTT\t6"
+        );
+    }
+
+    /// Read replication strand regions from BED lines
+    fn replication_strand(bed: &str) -> Result<ReplicationStrandCounts, anyhow::Error> {
+        let path = std::env::temp_dir().join(format!(
+            "contextcounter-{}-{}.bed",
+            std::process::id(),
+            bed.len()
+        ));
+        std::fs::write(&path, bed).unwrap();
+        let counts = ReplicationStrandCounts::from_bed(&path);
+        std::fs::remove_file(&path).unwrap();
+        counts
+    }
+
+    fn nonzero_rows(table: &impl fmt::Display) -> Vec<String> {
+        table
+            .to_string()
+            .lines()
+            .filter(|row| !row.ends_with("\t0"))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn leading_and_lagging_strands() {
+        // The late bin has a leftward fork over 10-20 and a rightward one over 15-25: where they
+        // overlap nothing is counted
+        let bed = "track name=forks\n\
+            chr1\t0\t10\tright\tearly\n\
+            chr1\t10\t20\tleft\tlate\n\
+            chr1\t15\t25\tR\tlate\n\
+            chr1\t20\t30\t+\tearly\n";
+        for (base, early, late_left, late_right) in [
+            (b'T', "lagging", "leading", "lagging"),
+            (b'A', "leading", "lagging", "leading"),
+        ] {
+            let mut counts = replication_strand(bed).unwrap();
+            assert_eq!(counts.bins(), 2);
+            counts.count_contig("chr1", &[base; 30], None, false);
+            counts.count_contig("chr2", &[base; 30], None, false);
+
+            // Rightward forks cover centres 1-9 and 20-28 of the early bin and 20-24 of the
+            // late bin, the leftward fork centres 10-14
+            let mut expected = vec![
+                "bin\tstrand\tcontext\tcount".to_string(),
+                format!("early\t{early}\tTTT\t18"),
+                format!("late\t{late_left}\tTTT\t5"),
+                format!("late\t{late_right}\tTTT\t5"),
+            ];
+            // Leading rows come before lagging ones
+            expected[2..].sort_by_key(|row| row.contains("lagging"));
+            assert_eq!(nonzero_rows(&counts), expected, "{}", base as char);
+        }
+    }
+
+    #[test]
+    fn fork_direction_columns() {
+        let counts = replication_strand("chr1\t0\t10\t-\nchr1\t20\t30\tL\n").unwrap();
+        assert_eq!(counts.bins(), 1);
+        assert!(
+            counts
+                .to_string()
+                .lines()
+                .nth(1)
+                .unwrap()
+                .starts_with("all\tleading\t")
+        );
+
+        assert!(replication_strand("chr1\t0\t10\n").is_err());
+        assert!(replication_strand("chr1\t0\t10\tup\n").is_err());
+    }
+}
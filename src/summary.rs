@@ -0,0 +1,224 @@
+use crate::counts::{Counts, OtherCounts, is_invalid_base};
+use log::{info, warn};
+use std::fmt;
+
+/// Number of invalid characters whose positions are logged and written to the summary
+const MAX_INVALID_REPORTED: usize = 10;
+
+/// Breakdown of each counter's 'other' windows by contig, and the first invalid characters seen
+#[derive(Debug, Default, Clone)]
+pub struct OtherSummary {
+    /// Each counter's breakdown before the current contig
+    before: Vec<OtherCounts>,
+    /// Each counted contig with the breakdown of each counter's windows in it
+    contigs: Vec<(String, Vec<OtherCounts>)>,
+    /// Contig, position (1-based) and byte of the first invalid characters
+    invalid_bases: Vec<(String, usize, u8)>,
+    /// Number of invalid characters seen
+    invalid_total: u64,
+}
+
+impl OtherSummary {
+    /// Note each counter's breakdown before a contig is counted
+    pub fn start_contig(&mut self, counters: &[&mut Counts]) {
+        self.before = counters
+            .iter()
+            .map(|counter| counter.other_breakdown())
+            .collect();
+    }
+
+    /// Record the breakdown of each counter's windows in the contig counted since
+    /// [`OtherSummary::start_contig`]
+    pub fn finish_contig(&mut self, contig_name: &str, counters: &[&mut Counts]) {
+        let breakdowns: Vec<OtherCounts> = counters
+            .iter()
+            .zip(&self.before)
+            .map(|(counter, &before)| counter.other_breakdown() - before)
+            .collect();
+        // Log the first (trinucleotide) table only; every table is in the summary file
+        if let Some(other) = breakdowns.first().filter(|other| other.total() > 0) {
+            info!(
+                "Contig: {} other trinucleotides: {} with N, {} with IUPAC codes, {} with invalid characters, {} soft-masked",
+                contig_name, other.n, other.iupac, other.invalid, other.masked
+            );
+        }
+        self.contigs.push((contig_name.to_string(), breakdowns));
+    }
+
+    /// Record invalid characters in bases starting at 0-based contig position `start`
+    pub fn scan_invalid(&mut self, contig_name: &str, start: usize, seq: &[u8]) {
+        for (offset, &base) in seq.iter().enumerate() {
+            if !is_invalid_base(base) {
+                continue;
+            }
+            self.invalid_total += 1;
+            if self.invalid_bases.len() < MAX_INVALID_REPORTED {
+                let position = start + offset + 1;
+                warn!(
+                    "Invalid character '{}' at {}:{}",
+                    base.escape_ascii(),
+                    contig_name,
+                    position
+                );
+                self.invalid_bases
+                    .push((contig_name.to_string(), position, base));
+            }
+        }
+    }
+
+    /// Number of invalid characters seen
+    pub fn invalid_total(&self) -> u64 {
+        self.invalid_total
+    }
+
+    /// Breakdown of each counter's windows over all contigs
+    pub fn totals(&self) -> Vec<OtherCounts> {
+        let mut totals = vec![OtherCounts::default(); self.before.len()];
+        for (_, breakdowns) in &self.contigs {
+            for (total, &breakdown) in totals.iter_mut().zip(breakdowns) {
+                *total += breakdown;
+            }
+        }
+        totals
+    }
+
+    /// Table of each contig's breakdowns, with counters named by `labels`
+    pub fn table<'a>(&'a self, labels: &'a [String]) -> OtherTable<'a> {
+        OtherTable {
+            summary: self,
+            labels,
+        }
+    }
+
+    /// Table of the first invalid characters seen
+    pub fn invalid_bases(&self) -> InvalidBases<'_> {
+        InvalidBases(self)
+    }
+}
+
+/// Per-contig breakdown of each counter's 'other' windows (see [`OtherSummary::table`])
+pub struct OtherTable<'a> {
+    summary: &'a OtherSummary,
+    labels: &'a [String],
+}
+
+impl OtherTable<'_> {
+    /// Core printer: writes a per-contig, per-counter table (plus totals) with the given delimiter
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(
+            f,
+            "contig{d}window{d}N{d}IUPAC{d}invalid{d}masked{d}other",
+            d = delim
+        )?;
+
+        let totals = self.summary.totals();
+        let rows = self
+            .summary
+            .contigs
+            .iter()
+            .map(|(contig, breakdowns)| (contig.as_str(), breakdowns));
+        for (contig, breakdowns) in rows.chain([("all", &totals)]) {
+            for (label, other) in self.labels.iter().zip(breakdowns) {
+                writeln!(
+                    f,
+                    "{contig}{d}{label}{d}{}{d}{}{d}{}{d}{}{d}{}",
+                    other.n,
+                    other.iupac,
+                    other.invalid,
+                    other.masked,
+                    other.total(),
+                    d = delim
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OtherTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+/// Positions of the first invalid characters seen (see [`OtherSummary::invalid_bases`])
+pub struct InvalidBases<'a>(&'a OtherSummary);
+
+impl InvalidBases<'_> {
+    /// Core printer: writes a (contig, position, character) table with the given delimiter
+    pub fn fmt_with_delimiter(&self, f: &mut fmt::Formatter<'_>, delim: char) -> fmt::Result {
+        // header
+        writeln!(f, "contig{d}position{d}character", d = delim)?;
+        for (contig, position, base) in &self.0.invalid_bases {
+            writeln!(
+                f,
+                "{contig}{d}{position}{d}{}",
+                base.escape_ascii(),
+                d = delim
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for InvalidBases<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // default to tab-delimited
+        self.fmt_with_delimiter(f, '\t')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breakdown_of_each_contig() {
+        let (mut mono, mut tri) = (Counts::new(1), Counts::new(3));
+        let mut summary = OtherSummary::default();
+        for (contig, seq) in [("chr1", &b"ACNGT"[..]), ("chr2", b"ARG-T")] {
+            let mut counters = [&mut mono, &mut tri];
+            summary.start_contig(&counters);
+            for counter in counters.iter_mut() {
+                counter.count_sequence(seq);
+            }
+            summary.scan_invalid(contig, 0, seq);
+            summary.finish_contig(contig, &counters);
+        }
+
+        // Windows are attributed to the first reason, so RG- and G-T are invalid, ARG IUPAC
+        let labels = ["mono".to_string(), "tri".to_string()];
+        assert_eq!(
+            summary.table(&labels).to_string(),
+            "contig\twindow\tN\tIUPAC\tinvalid\tmasked\tother\n\
+             chr1\tmono\t1\t0\t0\t0\t1\n\
+             chr1\ttri\t3\t0\t0\t0\t3\n\
+             chr2\tmono\t0\t1\t1\t0\t2\n\
+             chr2\ttri\t0\t1\t2\t0\t3\n\
+             all\tmono\t1\t1\t1\t0\t3\n\
+             all\ttri\t3\t1\t2\t0\t6\n"
+        );
+        assert_eq!(summary.totals()[1], tri.other_breakdown());
+        assert_eq!(
+            summary.invalid_bases().to_string(),
+            "contig\tposition\tcharacter\nchr2\t4\t-\n"
+        );
+    }
+
+    #[test]
+    fn first_invalid_characters_are_reported() {
+        let mut summary = OtherSummary::default();
+        summary.scan_invalid("chr1", 0, b"A*C*");
+        summary.scan_invalid("chr1", 100, &[b'*'; 20]);
+
+        assert_eq!(summary.invalid_total(), 22);
+        let table = summary.invalid_bases().to_string();
+        let rows: Vec<&str> = table.lines().skip(1).collect();
+        assert_eq!(rows.len(), MAX_INVALID_REPORTED);
+        // 1-based positions from the start of each scanned run
+        assert_eq!(rows[..3], ["chr1\t2\t*", "chr1\t4\t*", "chr1\t101\t*"]);
+        assert_eq!(rows[MAX_INVALID_REPORTED - 1], "chr1\t108\t*");
+    }
+}
@@ -0,0 +1,465 @@
+use crate::{
+    fasta::{Block, BlockSource},
+    regions::Interval,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// First 4 bytes of a .2bit file, in the byte order the file was written with
+const SIGNATURE: u32 = 0x1A41_2743;
+
+/// Streaming reader for UCSC `.2bit` files.
+///
+/// Contigs are yielded as [`Block`]s like [`crate::fasta::ContigBlocks`], but only the stretches
+/// between N blocks are decoded: each contig is split into its N-free segments (themselves split
+/// into blocks of `block_size` bases, overlapping by `overlap`), followed by an empty block at the
+/// contig end. Windows touching an N block are therefore never seen by a counter and must be
+/// accounted for separately. With a `block_size` of `usize::MAX` each contig is instead yielded
+/// whole, with N blocks filled in. Soft-masked blocks are decoded in lowercase.
+pub struct TwoBitBlocks {
+    file: BufReader<File>,
+    big_endian: bool,
+    /// Name and file offset of each contig record
+    contigs: Vec<(String, u64)>,
+    block_size: usize,
+    overlap: usize,
+    /// Index of the next contig to load
+    next: usize,
+    contig: Option<Contig>,
+    seq: Vec<u8>,
+}
+
+/// Layout of the contig currently being read
+struct Contig {
+    name: String,
+    length: usize,
+    n_blocks: Vec<Interval>,
+    mask_blocks: Vec<Interval>,
+    /// File offset of the packed bases
+    dna_offset: u64,
+    /// Remaining (carried, new bases) ranges to yield, in order
+    pieces: Vec<(usize, Interval)>,
+    yielded: usize,
+}
+
+impl TwoBitBlocks {
+    /// Whether a file starts with the .2bit signature
+    pub fn detect(path: &Path) -> io::Result<bool> {
+        let mut magic = [0; 4];
+        let mut file = File::open(path)?;
+        match file.read_exact(&mut magic) {
+            Ok(()) => Ok(
+                u32::from_le_bytes(magic) == SIGNATURE || u32::from_be_bytes(magic) == SIGNATURE
+            ),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Open a .2bit file and read its index of contigs
+    pub fn open(path: &Path, block_size: usize, overlap: usize) -> io::Result<Self> {
+        let mut reader = Self {
+            file: BufReader::new(File::open(path)?),
+            big_endian: false,
+            contigs: Vec::new(),
+            block_size: block_size.max(1),
+            overlap,
+            next: 0,
+            contig: None,
+            seq: Vec::new(),
+        };
+
+        let mut magic = [0; 4];
+        reader.file.read_exact(&mut magic)?;
+        reader.big_endian = if u32::from_le_bytes(magic) == SIGNATURE {
+            false
+        } else if u32::from_be_bytes(magic) == SIGNATURE {
+            true
+        } else {
+            return Err(invalid_data("missing .2bit signature"));
+        };
+
+        // Version 1 files use 64-bit record offsets
+        let version = reader.read_u32()?;
+        if version > 1 {
+            return Err(invalid_data(format!("unsupported .2bit version {version}")));
+        }
+        let contig_count = reader.read_u32()?;
+        let _reserved = reader.read_u32()?;
+
+        for _ in 0..contig_count {
+            let mut name_length = [0; 1];
+            reader.file.read_exact(&mut name_length)?;
+            let mut name = vec![0; name_length[0] as usize];
+            reader.file.read_exact(&mut name)?;
+            let offset = match version {
+                0 => u64::from(reader.read_u32()?),
+                _ => reader.read_u64()?,
+            };
+            let name = String::from_utf8(name).map_err(invalid_data)?;
+            reader.contigs.push((name, offset));
+        }
+
+        Ok(reader)
+    }
+
+    /// Read the record header of the next contig and plan the blocks to yield
+    fn load_contig(&mut self) -> io::Result<Option<Contig>> {
+        let Some((name, offset)) = self.contigs.get(self.next).cloned() else {
+            return Ok(None);
+        };
+        self.next += 1;
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        let length = self.read_u32()? as usize;
+        let n_blocks = self.read_blocks()?;
+        let mask_blocks = self.read_blocks()?;
+        let _reserved = self.read_u32()?;
+        let dna_offset = self.file.stream_position()?;
+
+        let mut pieces = Vec::new();
+        if self.block_size == usize::MAX {
+            pieces.push((
+                0,
+                Interval {
+                    start: 0,
+                    end: length,
+                },
+            ));
+        } else {
+            // N-free segments, split into overlapping blocks
+            let mut segment_start = 0;
+            let gaps = n_blocks.iter().copied().chain([Interval {
+                start: length,
+                end: length,
+            }]);
+            for gap in gaps {
+                let mut start = segment_start;
+                while start < gap.start {
+                    let end = gap.start.min(start.saturating_add(self.block_size));
+                    let carried = self.overlap.min(start - segment_start);
+                    pieces.push((carried, Interval { start, end }));
+                    start = end;
+                }
+                segment_start = segment_start.max(gap.end);
+            }
+            // The final (empty) block marks the end of the contig
+            pieces.push((
+                0,
+                Interval {
+                    start: length,
+                    end: length,
+                },
+            ));
+        }
+
+        Ok(Some(Contig {
+            name,
+            length,
+            n_blocks,
+            mask_blocks,
+            dna_offset,
+            pieces,
+            yielded: 0,
+        }))
+    }
+
+    /// Read a count followed by arrays of block starts and sizes, as sorted intervals
+    fn read_blocks(&mut self) -> io::Result<Vec<Interval>> {
+        let count = self.read_u32()? as usize;
+        let starts = (0..count)
+            .map(|_| self.read_u32())
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut blocks = starts
+            .into_iter()
+            .map(|start| {
+                let size = self.read_u32()?;
+                Ok(Interval {
+                    start: start as usize,
+                    end: start as usize + size as usize,
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        blocks.sort_by_key(|block| block.start);
+        Ok(blocks)
+    }
+
+    /// Decode a range of the current contig into `self.seq`
+    fn decode(&mut self, range: Interval) -> io::Result<()> {
+        let Some(contig) = &self.contig else {
+            return Ok(());
+        };
+        self.seq.clear();
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        // Four bases per byte, first base in the most significant bits (T=0, C=1, A=2, G=3)
+        let mut packed = vec![0; range.end.div_ceil(4) - range.start / 4];
+        self.file.seek(SeekFrom::Start(
+            contig.dna_offset + (range.start / 4) as u64,
+        ))?;
+        self.file.read_exact(&mut packed)?;
+        self.seq.extend((range.start..range.end).map(|pos| {
+            let byte = packed[pos / 4 - range.start / 4];
+            b"TCAG"[((byte >> (6 - 2 * (pos % 4))) & 3) as usize]
+        }));
+
+        let overlapping = |blocks: &[Interval]| {
+            let first = blocks.partition_point(|block| block.end <= range.start);
+            blocks[first..]
+                .iter()
+                .take_while(|block| block.start < range.end)
+                .map(|block| {
+                    block.start.max(range.start) - range.start
+                        ..block.end.min(range.end) - range.start
+                })
+                .collect::<Vec<_>>()
+        };
+        for masked in overlapping(&contig.mask_blocks) {
+            self.seq[masked].make_ascii_lowercase();
+        }
+        for n in overlapping(&contig.n_blocks) {
+            self.seq[n].fill(b'N');
+        }
+        Ok(())
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.file.read_exact(&mut bytes)?;
+        Ok(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0; 8];
+        self.file.read_exact(&mut bytes)?;
+        Ok(match self.big_endian {
+            true => u64::from_be_bytes(bytes),
+            false => u64::from_le_bytes(bytes),
+        })
+    }
+}
+
+impl BlockSource for TwoBitBlocks {
+    fn next_block(&mut self) -> io::Result<Option<Block<'_>>> {
+        // Move on to the next contig once every block of the current one is yielded
+        if self
+            .contig
+            .as_ref()
+            .is_none_or(|contig| contig.yielded == contig.pieces.len())
+        {
+            self.contig = self.load_contig()?;
+        }
+        let Some(contig) = &self.contig else {
+            return Ok(None);
+        };
+
+        let i = contig.yielded;
+        let (carried, piece) = contig.pieces[i];
+        let last = i + 1 == contig.pieces.len();
+        let range = Interval {
+            start: piece.start - carried,
+            end: piece.end,
+        };
+        self.decode(range)?;
+
+        let contig = self.contig.as_mut().expect("contig is loaded");
+        contig.yielded += 1;
+        debug_assert!(!last || range.end == contig.length);
+        Ok(Some(Block {
+            contig: &contig.name,
+            start: range.start,
+            carried,
+            seq: &self.seq,
+            first: i == 0,
+            last,
+        }))
+    }
+}
+
+fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+
+    /// (start, size) of each N or soft-masked block
+    type Blocks = &'static [(usize, usize)];
+
+    /// A block read back as (start, carried, seq, last)
+    type ReadBlock = (usize, usize, Vec<u8>, bool);
+
+    /// Contigs of uppercase bases, with N blocks and soft-masked blocks
+    const CONTIGS: [(&str, &str, Blocks, Blocks); 2] = [
+        ("chr1", "ACGTACGTTGCAAC", &[(4, 3)], &[(0, 2), (10, 2)]),
+        ("chrM", "GATTACA", &[(0, 2), (6, 1)], &[]),
+    ];
+
+    /// Expected sequences, with N blocks filled in and masked bases in lowercase
+    fn expected(seq: &str, n_blocks: Blocks, mask_blocks: Blocks) -> Vec<u8> {
+        let mut seq = seq.as_bytes().to_vec();
+        for &(start, size) in mask_blocks {
+            seq[start..start + size].make_ascii_lowercase();
+        }
+        for &(start, size) in n_blocks {
+            seq[start..start + size].fill(b'N');
+        }
+        seq
+    }
+
+    /// Write [`CONTIGS`] as a .2bit file of the given version and byte order
+    fn write(version: u32, big_endian: bool) -> PathBuf {
+        let u32_bytes = |value: u32| match big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        let offset_size = if version == 0 { 4 } else { 8 };
+        let mut offset = 16
+            + CONTIGS
+                .iter()
+                .map(|(name, ..)| 1 + name.len() + offset_size)
+                .sum::<usize>();
+
+        let mut header = Vec::new();
+        for value in [SIGNATURE, version, CONTIGS.len() as u32, 0] {
+            header.extend(u32_bytes(value));
+        }
+        let mut records = Vec::new();
+        for (name, seq, n_blocks, mask_blocks) in CONTIGS {
+            header.push(name.len() as u8);
+            header.extend(name.as_bytes());
+            match (version, big_endian) {
+                (0, _) => header.extend(u32_bytes(offset as u32)),
+                (_, true) => header.extend((offset as u64).to_be_bytes()),
+                (_, false) => header.extend((offset as u64).to_le_bytes()),
+            }
+
+            let mut record = u32_bytes(seq.len() as u32).to_vec();
+            for blocks in [n_blocks, mask_blocks] {
+                record.extend(u32_bytes(blocks.len() as u32));
+                for &(start, _) in blocks {
+                    record.extend(u32_bytes(start as u32));
+                }
+                for &(_, size) in blocks {
+                    record.extend(u32_bytes(size as u32));
+                }
+            }
+            record.extend(u32_bytes(0));
+            // Bases under N blocks are stored as T (0)
+            let codes = expected(seq, n_blocks, &[])
+                .into_iter()
+                .map(|base| match base {
+                    b'C' => 1,
+                    b'A' => 2,
+                    b'G' => 3,
+                    _ => 0,
+                });
+            let codes: Vec<u8> = codes.collect();
+            record.extend(codes.chunks(4).map(|chunk| {
+                (0..4).fold(0, |byte, i| {
+                    (byte << 2) | chunk.get(i).copied().unwrap_or(0)
+                })
+            }));
+            offset += record.len();
+            records.push(record);
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "contextcounter-{}-v{version}-{}.2bit",
+            std::process::id(),
+            if big_endian { "be" } else { "le" }
+        ));
+        fs::write(&path, [header, records.concat()].concat()).unwrap();
+        path
+    }
+
+    /// Each contig's blocks
+    fn read(path: &Path, block_size: usize, overlap: usize) -> Vec<(String, Vec<ReadBlock>)> {
+        let mut blocks = TwoBitBlocks::open(path, block_size, overlap).unwrap();
+        let mut contigs: Vec<(String, Vec<_>)> = Vec::new();
+        while let Some(block) = blocks.next_block().unwrap() {
+            if block.first {
+                contigs.push((block.contig.to_string(), Vec::new()));
+            }
+            let (_, contig_blocks) = contigs.last_mut().unwrap();
+            contig_blocks.push((block.start, block.carried, block.seq.to_vec(), block.last));
+        }
+        contigs
+    }
+
+    #[test]
+    fn versions_and_byte_orders() {
+        for version in [0, 1] {
+            for big_endian in [false, true] {
+                let path = write(version, big_endian);
+                assert!(TwoBitBlocks::detect(&path).unwrap());
+                let contigs = read(&path, usize::MAX, 2);
+                fs::remove_file(&path).unwrap();
+
+                assert_eq!(contigs.len(), CONTIGS.len());
+                for ((name, blocks), (expected_name, seq, n_blocks, mask_blocks)) in
+                    contigs.into_iter().zip(CONTIGS)
+                {
+                    assert_eq!(name, expected_name);
+                    let seq = expected(seq, n_blocks, mask_blocks);
+                    assert_eq!(blocks, [(0, 0, seq, true)], "version {version}, {name}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn n_blocks_are_skipped() {
+        let path = write(1, false);
+        let contigs = read(&path, 3, 2);
+        fs::remove_file(&path).unwrap();
+
+        // chr1: ACGTACGTTGCAAC with an N block at 4-7, masked at 0-2 and 10-12
+        let (name, blocks) = &contigs[0];
+        assert_eq!(name, "chr1");
+        let block = |start, carried, seq: &[u8], last| (start, carried, seq.to_vec(), last);
+        assert_eq!(
+            blocks,
+            &[
+                block(0, 0, b"acG", false),
+                block(1, 2, b"cGT", false),
+                block(7, 0, b"TTG", false),
+                block(8, 2, b"TGcaA", false),
+                block(11, 2, b"aAC", false),
+                block(14, 0, b"", true),
+            ]
+        );
+
+        // chrM: GATTACA with N blocks at 0-2 and 6-7
+        let (name, blocks) = &contigs[1];
+        assert_eq!(name, "chrM");
+        assert_eq!(
+            blocks,
+            &[
+                block(2, 0, b"TTA", false),
+                block(3, 2, b"TAC", false),
+                block(7, 0, b"", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_files() {
+        let path = write(2, false);
+        assert!(TwoBitBlocks::open(&path, usize::MAX, 0).is_err());
+        fs::write(&path, b">chr1\nACGT\n").unwrap();
+        assert!(!TwoBitBlocks::detect(&path).unwrap());
+        assert!(TwoBitBlocks::open(&path, usize::MAX, 0).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{
+    counts::{Counts, CountsDi, CountsPenta, CountsTri},
+    io::open_maybe_compressed,
+    regions::{Interval, flank_pad, is_bed_header, parse_bed_line},
+};
+use anyhow::{Context, bail};
+use std::{collections::HashMap, io::BufRead, path::Path};
+
+/// Per-base weights (e.g. the probability of detecting a mutation, from depth and tumour purity)
+/// grouped by contig. Bases outside the track have a weight of 0.
+#[derive(Debug, Default, Clone)]
+pub struct WeightTrack {
+    contigs: HashMap<String, Vec<(Interval, f64)>>,
+}
+
+impl WeightTrack {
+    /// Read a bedGraph (plain or gzip compressed) with the weight of each interval in column 4.
+    /// Intervals of a contig must not overlap.
+    pub fn from_bedgraph(path: &Path) -> Result<Self, anyhow::Error> {
+        let (reader, _) = open_maybe_compressed(path, 1)
+            .with_context(|| format!("Failed to open weight track: {}", path.display()))?;
+
+        let mut track = WeightTrack::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line =
+                line.with_context(|| format!("Failed to read weight track: {}", path.display()))?;
+            if is_bed_header(&line) {
+                continue;
+            }
+
+            let parsed = parse_bed_line(&line).and_then(|(contig, interval)| {
+                let Some(weight) = line.split('\t').nth(3) else {
+                    bail!("expected a 4th column with the weight");
+                };
+                let weight: f64 = weight
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("weight is not a number: '{weight}'"))?;
+                Ok((contig, interval, weight))
+            });
+            let (contig, interval, weight) =
+                parsed.with_context(|| format!("Invalid line {} in {}", i + 1, path.display()))?;
+            if weight != 0.0 && !interval.is_empty() {
+                track
+                    .contigs
+                    .entry(contig.to_string())
+                    .or_default()
+                    .push((interval, weight));
+            }
+        }
+
+        for (contig, runs) in &mut track.contigs {
+            runs.sort_unstable_by_key(|(interval, _)| *interval);
+            if let Some(pair) = runs.windows(2).find(|pair| pair[0].0.end > pair[1].0.start) {
+                bail!(
+                    "Overlapping intervals in weight track {}: {}:{}-{} and {}:{}-{}",
+                    path.display(),
+                    contig,
+                    pair[0].0.start,
+                    pair[0].0.end,
+                    contig,
+                    pair[1].0.start,
+                    pair[1].0.end
+                );
+            }
+        }
+        Ok(track)
+    }
+
+    /// Number of intervals across all contigs
+    pub fn len(&self) -> usize {
+        self.contigs.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Looks up weights along a contig, for positions queried in increasing order
+struct WeightCursor<'a> {
+    runs: &'a [(Interval, f64)],
+}
+
+impl WeightCursor<'_> {
+    fn weight(&mut self, position: usize) -> f64 {
+        let passed = self
+            .runs
+            .iter()
+            .take_while(|(interval, _)| interval.end <= position)
+            .count();
+        self.runs = &self.runs[passed..];
+        match self.runs.first() {
+            Some((interval, weight)) if interval.start <= position => *weight,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Di/tri/pentanucleotide counts where each window adds the weight of its central base instead
+/// of 1 (the mean weight of the two central bases for dinucleotides). Windows with a weight of
+/// 0 are not counted at all; 'other' is the number of weighted windows that could not be counted.
+#[derive(Debug, Clone)]
+pub struct WeightedCounts {
+    track: WeightTrack,
+    trinucleotides: CountsTri,
+    pentanucleotides: CountsPenta,
+    dinucleotides: CountsDi,
+}
+
+impl WeightedCounts {
+    pub fn new(track: WeightTrack) -> Self {
+        Self {
+            track,
+            trinucleotides: CountsTri::default(),
+            pentanucleotides: CountsPenta::default(),
+            dinucleotides: CountsDi::default(),
+        }
+    }
+
+    /// The tri-, penta- and dinucleotide tables, e.g. to set their soft-mask mode
+    pub fn tables_mut(&mut self) -> [&mut Counts; 3] {
+        [
+            &mut self.trinucleotides,
+            &mut self.pentanucleotides,
+            &mut self.dinucleotides,
+        ]
+    }
+
+    pub fn trinucleotides(&self) -> &CountsTri {
+        &self.trinucleotides
+    }
+
+    pub fn pentanucleotides(&self) -> &CountsPenta {
+        &self.pentanucleotides
+    }
+
+    pub fn dinucleotides(&self) -> &CountsDi {
+        &self.dinucleotides
+    }
+
+    /// Count weighted contexts on a contig. If `intervals` is supplied only windows inside those
+    /// regions are counted (or, with `flank_context`, windows whose central base(s) are inside a
+    /// region). Intervals should be merged and clipped to the contig length.
+    pub fn count_contig(
+        &mut self,
+        contig: &str,
+        seq_bytes: &[u8],
+        intervals: Option<&[Interval]>,
+        flank_context: bool,
+    ) {
+        let Some(runs) = self.track.contigs.get(contig) else {
+            return;
+        };
+        let whole_contig = [Interval {
+            start: 0,
+            end: seq_bytes.len(),
+        }];
+        let intervals = intervals.unwrap_or(&whole_contig);
+
+        let tables: [&mut Counts; 3] = [
+            &mut self.trinucleotides,
+            &mut self.pentanucleotides,
+            &mut self.dinucleotides,
+        ];
+        for counts in tables {
+            let k = counts.k();
+            let pad = flank_pad(k, flank_context);
+            for interval in intervals {
+                let Interval { start, end } = interval.flanked(pad, seq_bytes.len());
+                let first = runs.partition_point(|(run, _)| run.end <= start);
+                let mut cursor = WeightCursor {
+                    runs: &runs[first..],
+                };
+
+                for window_start in start..(end + 1).saturating_sub(k) {
+                    let centre = window_start + k / 2;
+                    let weight = if k % 2 == 1 {
+                        cursor.weight(centre)
+                    } else {
+                        (cursor.weight(centre - 1) + cursor.weight(centre)) / 2.0
+                    };
+                    if weight != 0.0 {
+                        counts
+                            .increment_weighted(&seq_bytes[window_start..window_start + k], weight);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(start: usize, end: usize) -> Interval {
+        Interval { start, end }
+    }
+
+    /// Weights 1 over chr1:0-4 and 0.5 over chr1:4-6
+    fn track() -> WeightTrack {
+        let mut track = WeightTrack::default();
+        track.contigs.insert(
+            "chr1".to_string(),
+            vec![(interval(0, 4), 1.0), (interval(4, 6), 0.5)],
+        );
+        track
+    }
+
+    #[test]
+    fn cursor_across_runs() {
+        let runs = [
+            (interval(2, 4), 1.0),
+            (interval(4, 6), 2.0),
+            (interval(8, 9), 3.0),
+        ];
+        let mut cursor = WeightCursor { runs: &runs };
+        let weights: Vec<f64> = (0..11).map(|position| cursor.weight(position)).collect();
+        assert_eq!(
+            weights,
+            [0.0, 0.0, 1.0, 1.0, 2.0, 2.0, 0.0, 0.0, 3.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn windows_add_the_weight_of_their_centre() {
+        let mut counts = WeightedCounts::new(track());
+        counts.count_contig("chr1", b"ACGTACGTAA", None, false);
+        counts.count_contig("chr2", b"ACGTACGTAA", None, false);
+
+        // Centres 1-5 are weighted (ACG, CGT = ACG, GTA, TAC = GTA, ACG); windows centred on
+        // unweighted bases are skipped, not counted as other
+        let tri = counts.trinucleotides();
+        assert_eq!(tri.weighted_count("ACG"), Some(2.5));
+        assert_eq!(tri.weighted_count("GTA"), Some(1.5));
+        assert_eq!(tri.total(true), 5);
+        assert_eq!(tri.other(), 0);
+
+        // Dinucleotides take the mean of their two central bases: TA at 3-4 weighs 0.75,
+        // CG at 5-6 weighs 0.25
+        let di = counts.dinucleotides();
+        assert_eq!(di.weighted_count("TA"), Some(0.75));
+        assert_eq!(di.weighted_count("CG"), Some(1.25));
+        assert_eq!(di.total(true), 6);
+    }
+
+    #[test]
+    fn weighted_windows_in_intervals() {
+        let mut counts = WeightedCounts::new(track());
+        counts.count_contig(
+            "chr1",
+            b"ACGTACGTAA",
+            Some(&[interval(0, 3), interval(4, 8)]),
+            false,
+        );
+        // ACG at 0-3 and 4-7 (CGT at 5-8 is centred on an unweighted base)
+        let tri = counts.trinucleotides();
+        assert_eq!(tri.weighted_count("ACG"), Some(1.5));
+        assert_eq!(tri.total(true), 2);
+    }
+
+    #[test]
+    fn bedgraph_tracks() {
+        let path =
+            std::env::temp_dir().join(format!("contextcounter-{}.bedgraph", std::process::id()));
+        std::fs::write(
+            &path,
+            "track type=bedGraph\nchr1\t5\t8\t0.5\nchr1\t0\t5\t1\nchr1\t8\t9\t0\nchr2\t0\t3\t2\n",
+        )
+        .unwrap();
+        let track = WeightTrack::from_bedgraph(&path);
+        std::fs::write(&path, "chr1\t0\t5\t1\nchr2\t0\t5\t1\nchr1\t4\t8\t2\n").unwrap();
+        let overlapping = WeightTrack::from_bedgraph(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        // Sorted, with zero weights dropped
+        let track = track.unwrap();
+        assert_eq!(track.len(), 3);
+        assert_eq!(
+            track.contigs["chr1"],
+            [(interval(0, 5), 1.0), (interval(5, 8), 0.5)]
+        );
+
+        let error = format!("{:#}", overlapping.unwrap_err());
+        assert!(
+            error.contains("Overlapping intervals") && error.contains("chr1:0-5 and chr1:4-8"),
+            "{error}"
+        );
+    }
+}